@@ -0,0 +1,57 @@
+use crate::{
+    board::BoardState, options::EngineOptions, search::SearchLimits, smp::lazy_smp,
+    tt::TranspositionTable,
+};
+use std::{
+    process::exit,
+    sync::{atomic::AtomicBool, Arc},
+    time::Instant,
+};
+
+pub const BENCH_FENS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+// searches the bench positions with 1..=options.threads threads and prints nodes per second
+pub fn bench(depth: u8, options: &EngineOptions) {
+    for threads in 1..=options.threads {
+        let thread_options = EngineOptions {
+            threads,
+            ..options.clone()
+        };
+
+        let mut nodes = 0;
+        let start = Instant::now();
+
+        for fen in BENCH_FENS.iter() {
+            let board = BoardState::from_fen(fen).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            let tt = Arc::new(TranspositionTable::new(options.hash_mb));
+
+            nodes += lazy_smp(
+                &board,
+                SearchLimits::depth(depth),
+                &thread_options,
+                tt,
+                Arc::new(AtomicBool::new(false)),
+            )
+            .nodes;
+        }
+
+        let elapsed_ms = start.elapsed().as_millis().max(1);
+
+        println!(
+            "threads: {}, nodes: {}, time: {} ms, nps: {}",
+            threads,
+            nodes,
+            elapsed_ms,
+            nodes as u128 * 1000 / elapsed_ms
+        );
+    }
+}
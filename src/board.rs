@@ -1,17 +1,22 @@
 use crate::{
-    constants::{BOARD_SQUARES, FIFTH_RANK, FOURTH_RANK},
+    constants::{BOARD_SQUARES, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS},
+    eval::PieceSquareTables,
+    eval_params::EvalParams,
     move_generation::{
         generate_bishop_moves, generate_king_moves, generate_knight_moves, generate_pawn_moves,
         generate_queen_moves, generate_rook_moves,
     },
+    nnue::{Network, NnueState},
     piece_parsing::parse_bitboards,
-    utils::{number_to_color, number_to_piece, opposite_color},
+    utils::{number_to_color, number_to_piece, opposite_color, square_to_string},
+    zobrist::{castling_key, compute_key, compute_pawn_key, en_passant_key, piece_key, side_key},
 };
-use std::process::exit;
+use std::{process::exit, sync::Arc};
 
 pub type EncodedMove = u32;
 pub type Bitboard = u64;
-pub type DecodedMove = (Bitboard, Bitboard, Piece, Color, Piece, bool);
+// the last piece is the one a pawn promotes to, Piece::None for every other move
+pub type DecodedMove = (Bitboard, Bitboard, Piece, Color, Piece, Piece);
 
 #[derive(Debug, Copy, Clone)]
 pub enum Color {
@@ -30,6 +35,14 @@ pub enum Piece {
     None = 6,
 }
 
+// everything make_move can't recover from the move itself
+#[derive(Debug, Clone, Copy)]
+pub struct UndoState {
+    pub bb_castling_rigths: [[Bitboard; 2]; 2],
+    pub bb_en_passant: Bitboard,
+    pub key: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct BoardState {
     pub bb_pieces: [[Bitboard; 6]; 2],
@@ -41,6 +54,16 @@ pub struct BoardState {
     pub halfmove: u32,
     pub fullmove: u32,
     pub move_history: Vec<EncodedMove>,
+    pub key: u64,
+    // only pawns are hashed, used by the pawn hash table
+    pub pawn_key: u64,
+    pub history: Vec<UndoState>,
+    // material and piece square totals by color, (middlegame, endgame), from psq_tables
+    pub material: [(i32, i32); 2],
+    pub piece_square: [(i32, i32); 2],
+    pub psq_tables: Arc<PieceSquareTables>,
+    // network accumulator, evaluate uses the network instead of the classical eval when set
+    pub nnue: Option<NnueState>,
 }
 
 impl BoardState {
@@ -64,7 +87,7 @@ impl BoardState {
         piece: Piece,
         color: Color,
         captured_piece: Piece,
-        is_promotion: bool,
+        promotion: Piece,
     ) -> Result<EncodedMove, &str> {
         if from_pos & !63 != 0 || to_pos & !63 != 0 {
             return Err("Incorrect move positions!");
@@ -75,8 +98,11 @@ impl BoardState {
             | ((to_pos as u32) & 63) << 6
             | ((piece as u32) & 7) << 12
             | (color as u32) << 15
-            | (captured_piece as u32) << 18
-            | (is_promotion as u32) << 19)
+            | (captured_piece as u32) << 16
+            | match promotion {
+                Piece::None => 0,
+                _ => 1 << 19 | ((promotion as u32) & 7) << 20,
+            })
     }
 
     pub fn decode_move(&self, piece_move: EncodedMove) -> Result<DecodedMove, &str> {
@@ -94,39 +120,161 @@ impl BoardState {
             println!("{}", err);
             exit(1);
         });
-        let is_promotion = (piece_move >> 19) & 1 == 1;
+        // moves without the promotion flag are encoded with 0 there, which is a pawn
+        let promotion: Piece = match (piece_move >> 19) & 1 {
+            1 => number_to_piece((piece_move >> 20) & 7).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            }),
+            _ => Piece::None,
+        };
 
-        Ok((start_bb, end_bb, piece, color, captured_piece, is_promotion))
+        Ok((start_bb, end_bb, piece, color, captured_piece, promotion))
     }
 
     pub fn is_in_check(&self, color: &Color) -> bool {
-        let all_attacks = self
-            .generate_moves_by_color(&opposite_color(*color))
-            .iter()
-            .fold(0, |all_moves, cur_move| {
-                all_moves | BOARD_SQUARES[((cur_move >> 6) & 63) as usize]
-            });
+        let bb_king = self.get_piece_bb(*color, Piece::King);
+
+        bb_king != 0 && self.is_square_attacked(bb_king.trailing_zeros(), opposite_color(*color))
+    }
+
+    // looks from the square with every kind of piece, so it doesn't need the moves of the
+    // other side and castling can ask it while moves are generated
+    pub fn is_square_attacked(&self, square: u32, by_color: Color) -> bool {
+        let defender = opposite_color(by_color);
+        let from_square = vec![(defender as u8, square as u8)];
+        let bb_queens = self.get_piece_bb(by_color, Piece::Queen);
+
+        let bb_bishop_lines = generate_bishop_moves(from_square.clone(), 0, self.bb_fullboard)[0].1;
+        let bb_rook_lines = generate_rook_moves(from_square, 0, self.bb_fullboard)[0].1;
+
+        PAWN_ATTACKS[defender as usize][square as usize] & self.get_piece_bb(by_color, Piece::Pawn)
+            != 0
+            || KNIGHT_ATTACKS[square as usize] & self.get_piece_bb(by_color, Piece::Knight) != 0
+            || KING_ATTACKS[square as usize] & self.get_piece_bb(by_color, Piece::King) != 0
+            || bb_bishop_lines & (self.get_piece_bb(by_color, Piece::Bishop) | bb_queens) != 0
+            || bb_rook_lines & (self.get_piece_bb(by_color, Piece::Rook) | bb_queens) != 0
+    }
+
+    // square the captured pawn stands on when a pawn takes en passant, None for other moves
+    fn en_passant_capture(
+        &self,
+        piece: Piece,
+        color: Color,
+        end_bb: Bitboard,
+        bb_en_passant: Bitboard,
+    ) -> Option<Bitboard> {
+        if !matches!(piece, Piece::Pawn) || end_bb != bb_en_passant || end_bb == 0 {
+            return None;
+        }
+
+        match color {
+            Color::White => Some(end_bb << 8),
+            Color::Black => Some(end_bb >> 8),
+        }
+    }
+
+    // the rook squares of a castling move, the king goes two files towards the rook
+    fn castling_rook(piece: Piece, start_bb: Bitboard, end_bb: Bitboard) -> Option<(usize, usize)> {
+        let from = start_bb.trailing_zeros() as usize;
+        let to = end_bb.trailing_zeros() as usize;
+
+        match piece {
+            Piece::King if to == from + 2 => Some((to + 1, to - 1)),
+            Piece::King if to + 2 == from => Some((to - 2, to + 1)),
+            _ => None,
+        }
+    }
+
+    // moves a piece that isn't the one encoded in the move, like the rook when castling
+    fn shift_piece(&mut self, color: Color, piece: Piece, from: usize, to: usize) {
+        self.bb_pieces[color as usize][piece as usize] ^= BOARD_SQUARES[from] | BOARD_SQUARES[to];
+        self.bb_colors[color as usize] ^= BOARD_SQUARES[from] | BOARD_SQUARES[to];
+        self.bb_fullboard = self.bb_colors[0] | self.bb_colors[1];
+
+        self.update_psq(color, piece, from, -1);
+        self.update_psq(color, piece, to, 1);
 
-        all_attacks & self.get_piece_bb(*color, Piece::King) != 0
+        if let Some(nnue) = &mut self.nnue {
+            nnue.make_move(color, piece, from, to, Piece::None);
+        }
+    }
+
+    // swaps the piece on a square for another of the same color, used by promotions
+    fn swap_piece(&mut self, color: Color, old_piece: Piece, new_piece: Piece, square: usize) {
+        self.bb_pieces[color as usize][old_piece as usize] &= !BOARD_SQUARES[square];
+        self.bb_pieces[color as usize][new_piece as usize] |= BOARD_SQUARES[square];
+
+        self.update_psq(color, old_piece, square, -1);
+        self.update_psq(color, new_piece, square, 1);
+
+        if let Some(nnue) = &mut self.nnue {
+            nnue.remove_piece(color, old_piece, square);
+            nnue.add_piece(color, new_piece, square);
+        }
     }
 
     pub fn make_move(&mut self, piece_move: EncodedMove) {
-        let (start_bb, end_bb, piece, color, captured_piece, _) =
+        let (start_bb, end_bb, piece, color, captured_piece, promotion) =
             self.decode_move(piece_move).unwrap_or_else(|err| {
                 println!("{}", err);
                 exit(1);
             });
+        let en_passant = self.en_passant_capture(piece, color, end_bb, self.bb_en_passant);
+        let capture_bb = en_passant.unwrap_or(end_bb);
+
+        self.history.push(UndoState {
+            bb_castling_rigths: self.bb_castling_rigths,
+            bb_en_passant: self.bb_en_passant,
+            key: self.key,
+        });
+
+        // castling and en passant keys get xored back in once they are updated
+        self.key ^= castling_key(self.bb_castling_rigths) ^ en_passant_key(self.bb_en_passant);
+
+        // en passant square is only valid for one move
+        self.bb_en_passant = 0;
 
-        // delete piece on the move square if there is one
+        // delete piece on the move square if there is one, en passant takes the pawn
+        // behind it
         if !matches!(captured_piece, Piece::None) {
             match color {
                 Color::White => {
-                    self.bb_pieces[1][captured_piece as usize] &= !end_bb;
+                    self.bb_pieces[1][captured_piece as usize] &= !capture_bb;
                 }
                 Color::Black => {
-                    self.bb_pieces[0][captured_piece as usize] &= !end_bb;
+                    self.bb_pieces[0][captured_piece as usize] &= !capture_bb;
                 }
             }
+
+            self.bb_colors[opposite_color(color) as usize] &= !capture_bb;
+
+            self.key ^= piece_key(
+                opposite_color(color),
+                captured_piece,
+                capture_bb.trailing_zeros(),
+            );
+
+            self.update_psq(
+                opposite_color(color),
+                captured_piece,
+                capture_bb.trailing_zeros() as usize,
+                -1,
+            );
+
+            if matches!(captured_piece, Piece::Pawn) {
+                self.pawn_key ^= piece_key(
+                    opposite_color(color),
+                    Piece::Pawn,
+                    capture_bb.trailing_zeros(),
+                );
+            }
+
+            // a rook taken on its square loses the castling right that goes with it
+            if matches!(captured_piece, Piece::Rook) {
+                self.bb_castling_rigths[opposite_color(color) as usize][0] &= !end_bb;
+                self.bb_castling_rigths[opposite_color(color) as usize][1] &= !end_bb;
+            }
         }
 
         // delete piece from color bitboards
@@ -178,74 +326,150 @@ impl BoardState {
         self.bb_colors[color as usize] |= end_bb;
         self.bb_fullboard |= end_bb;
 
+        self.key ^= piece_key(color, piece, start_bb.trailing_zeros())
+            ^ piece_key(color, piece, end_bb.trailing_zeros())
+            ^ castling_key(self.bb_castling_rigths)
+            ^ en_passant_key(self.bb_en_passant)
+            ^ side_key();
+
+        if matches!(piece, Piece::Pawn) {
+            self.pawn_key ^= piece_key(color, piece, start_bb.trailing_zeros())
+                ^ piece_key(color, piece, end_bb.trailing_zeros());
+        }
+
+        self.update_psq(color, piece, start_bb.trailing_zeros() as usize, -1);
+        self.update_psq(color, piece, end_bb.trailing_zeros() as usize, 1);
+
+        if let Some(nnue) = &mut self.nnue {
+            nnue.make_move(
+                color,
+                piece,
+                start_bb.trailing_zeros() as usize,
+                end_bb.trailing_zeros() as usize,
+                if en_passant.is_some() {
+                    Piece::None
+                } else {
+                    captured_piece
+                },
+            );
+
+            if en_passant.is_some() {
+                nnue.remove_piece(
+                    opposite_color(color),
+                    Piece::Pawn,
+                    capture_bb.trailing_zeros() as usize,
+                );
+            }
+        }
+
+        // the pawn already moved, it's swapped for the new piece on the last rank
+        if !matches!(promotion, Piece::None) {
+            let square = end_bb.trailing_zeros();
+
+            self.swap_piece(color, Piece::Pawn, promotion, square as usize);
+            self.key ^= piece_key(color, Piece::Pawn, square) ^ piece_key(color, promotion, square);
+            self.pawn_key ^= piece_key(color, Piece::Pawn, square);
+        }
+
+        if let Some((rook_from, rook_to)) = BoardState::castling_rook(piece, start_bb, end_bb) {
+            self.shift_piece(color, Piece::Rook, rook_from, rook_to);
+            self.key ^= piece_key(color, Piece::Rook, rook_from as u32)
+                ^ piece_key(color, Piece::Rook, rook_to as u32);
+        }
+
         self.move_history.push(piece_move);
 
-        // println!("{}", format!("{:016b}", encoded_move));
-        // println!("{:?}", piece);
-        // if black to move
+        // fullmove grows after black's move
+        if matches!(color, Color::Black) {
+            self.fullmove += 1;
+        }
+
         if self.halfmove == 1 {
-            self.to_move = Color::White;
             self.halfmove = 0;
-            self.fullmove += 1;
         } else {
-            self.to_move = Color::Black;
             self.halfmove = 1;
         }
+
+        self.to_move = opposite_color(color);
     }
 
     pub fn undo_move(&mut self) -> Result<(), &str> {
         let last_move = self.move_history.pop().expect("No more moves found!");
+        let last_state = self.history.pop().expect("No more moves found!");
 
-        let (start_bb, end_bb, piece, color, captured_piece, is_promotion) =
+        let (start_bb, end_bb, piece, color, captured_piece, promotion) =
             self.decode_move(last_move).unwrap_or_else(|err| {
                 println!("{}", err);
                 exit(1);
             });
+        let en_passant = self.en_passant_capture(piece, color, end_bb, last_state.bb_en_passant);
+        let capture_bb = en_passant.unwrap_or(end_bb);
 
-        // check for castling and castling avaliability
-        match piece {
-            Piece::Pawn => {
-                match color {
-                    Color::White => {
-                        // if piece was previosly of fourth rank
-                        if start_bb & FOURTH_RANK != 0 {
-                            self.bb_en_passant |= start_bb << 8;
-                        }
-                    }
-                    Color::Black => {
-                        // if piece was previosly of fifth rank
-                        if start_bb & FIFTH_RANK != 0 {
-                            self.bb_en_passant |= start_bb >> 8;
-                        }
-                    }
-                }
-            }
-            Piece::Rook => {
-                for index in 0..2 {
-                    if (self.bb_castling_rigths[color as usize][index] | start_bb).count_ones() == 1
-                    {
-                        self.bb_castling_rigths[color as usize][index] = start_bb;
-                    }
-                }
-            }
-            Piece::King => {
-                if matches!(color, Color::White) {
-                    // white kingside
-                    self.bb_castling_rigths[color as usize][0] =
-                        self.bb_pieces[color as usize][Piece::Rook as usize] & BOARD_SQUARES[63];
-                    // white queenside
-                    self.bb_castling_rigths[color as usize][1] =
-                        self.bb_pieces[color as usize][Piece::Rook as usize] & BOARD_SQUARES[56];
+        // castling rights, en passant and key are restored as they were before the move
+        self.bb_castling_rigths = last_state.bb_castling_rigths;
+        self.bb_en_passant = last_state.bb_en_passant;
+        self.key = last_state.key;
+
+        // special moves are taken back first, so the rest is undone like a plain move
+        if let Some((rook_from, rook_to)) = BoardState::castling_rook(piece, start_bb, end_bb) {
+            self.shift_piece(color, Piece::Rook, rook_to, rook_from);
+        }
+
+        if !matches!(promotion, Piece::None) {
+            self.swap_piece(
+                color,
+                promotion,
+                Piece::Pawn,
+                end_bb.trailing_zeros() as usize,
+            );
+            self.pawn_key ^= piece_key(color, Piece::Pawn, end_bb.trailing_zeros());
+        }
+
+        if matches!(piece, Piece::Pawn) {
+            self.pawn_key ^= piece_key(color, piece, start_bb.trailing_zeros())
+                ^ piece_key(color, piece, end_bb.trailing_zeros());
+        }
+
+        if matches!(captured_piece, Piece::Pawn) {
+            self.pawn_key ^= piece_key(
+                opposite_color(color),
+                Piece::Pawn,
+                capture_bb.trailing_zeros(),
+            );
+        }
+
+        self.update_psq(color, piece, end_bb.trailing_zeros() as usize, -1);
+        self.update_psq(color, piece, start_bb.trailing_zeros() as usize, 1);
+
+        if !matches!(captured_piece, Piece::None) {
+            self.update_psq(
+                opposite_color(color),
+                captured_piece,
+                capture_bb.trailing_zeros() as usize,
+                1,
+            );
+        }
+
+        if let Some(nnue) = &mut self.nnue {
+            nnue.undo_move(
+                color,
+                piece,
+                start_bb.trailing_zeros() as usize,
+                end_bb.trailing_zeros() as usize,
+                if en_passant.is_some() {
+                    Piece::None
                 } else {
-                    // black kingside
-                    self.bb_castling_rigths[color as usize][0] =
-                        self.bb_pieces[color as usize][Piece::Rook as usize] & BOARD_SQUARES[0];
-                    // black queenside
-                    self.bb_castling_rigths[color as usize][1] =
-                        self.bb_pieces[color as usize][Piece::Rook as usize] & BOARD_SQUARES[7];
-                }
+                    captured_piece
+                },
+            );
+
+            if en_passant.is_some() {
+                nnue.add_piece(
+                    opposite_color(color),
+                    Piece::Pawn,
+                    capture_bb.trailing_zeros() as usize,
+                );
             }
-            _ => (),
         }
 
         // undo move
@@ -259,9 +483,8 @@ impl BoardState {
         // if move captured piece
         if !matches!(captured_piece, Piece::None) {
             // if captured piece is not empty
-            self.bb_pieces[opposite_color(color) as usize][captured_piece as usize] |= end_bb;
-            self.bb_colors[opposite_color(color) as usize] |= end_bb;
-            self.bb_fullboard |= end_bb;
+            self.bb_pieces[opposite_color(color) as usize][captured_piece as usize] |= capture_bb;
+            self.bb_colors[opposite_color(color) as usize] |= capture_bb;
         }
 
         // undo move in fullboard
@@ -270,14 +493,17 @@ impl BoardState {
 
         // halfmove undo
         if self.halfmove == 0 {
-            // undo fullmove count only if white currently to move
             self.halfmove = 1;
-            self.fullmove -= 1;
         } else {
             self.halfmove = 0;
         }
 
-        self.to_move = opposite_color(self.to_move);
+        // undo fullmove count only if black made the move
+        if matches!(color, Color::Black) {
+            self.fullmove -= 1;
+        }
+
+        self.to_move = color;
 
         Ok(())
     }
@@ -304,21 +530,35 @@ impl BoardState {
             while move_bb != 0 {
                 let least_sign_bit = move_bb.trailing_zeros();
 
-                moves_vec.push(
-                    self.encode_move(
-                        piece_move.0.trailing_zeros() as u8,
-                        least_sign_bit as u8,
-                        Piece::Pawn,
-                        *color,
-                        self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
-                        false,
-                        // later implement promotion
-                    )
-                    .unwrap_or_else(|err| {
-                        println!("{}", err);
-                        exit(1);
-                    }),
-                );
+                // the pawn behind the en passant square is the one taken
+                let captured_piece =
+                    match BOARD_SQUARES[least_sign_bit as usize] == self.bb_en_passant {
+                        true => Piece::Pawn,
+                        false => self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
+                    };
+
+                // a pawn on the last rank becomes any of the four pieces, queen first
+                let promotions: &[Piece] = match least_sign_bit {
+                    0..=7 | 56..=63 => &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight],
+                    _ => &[Piece::None],
+                };
+
+                for &promotion in promotions {
+                    moves_vec.push(
+                        self.encode_move(
+                            piece_move.0.trailing_zeros() as u8,
+                            least_sign_bit as u8,
+                            Piece::Pawn,
+                            *color,
+                            captured_piece,
+                            promotion,
+                        )
+                        .unwrap_or_else(|err| {
+                            println!("{}", err);
+                            exit(1);
+                        }),
+                    );
+                }
 
                 move_bb ^= BOARD_SQUARES[least_sign_bit as usize];
             }
@@ -342,8 +582,7 @@ impl BoardState {
                         Piece::King,
                         *color,
                         self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
-                        false,
-                        // later implement promotion
+                        Piece::None,
                     )
                     .unwrap_or_else(|err| {
                         println!("{}", err);
@@ -355,6 +594,8 @@ impl BoardState {
             }
         }
 
+        moves_vec.extend(self.generate_castling_moves(*color));
+
         let knights_moves = generate_knight_moves(
             parse_bitboards(*color, self.get_piece_bb(*color, Piece::Knight)),
             self.get_color_bb(*color),
@@ -373,8 +614,7 @@ impl BoardState {
                         Piece::Knight,
                         *color,
                         self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
-                        false,
-                        // later implement promotion
+                        Piece::None,
                     )
                     .unwrap_or_else(|err| {
                         println!("{}", err);
@@ -405,8 +645,7 @@ impl BoardState {
                         Piece::Bishop,
                         *color,
                         self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
-                        false,
-                        // later implement promotion
+                        Piece::None,
                     )
                     .unwrap_or_else(|err| {
                         println!("{}", err);
@@ -436,8 +675,7 @@ impl BoardState {
                         Piece::Rook,
                         *color,
                         self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
-                        false,
-                        // later implement promotion
+                        Piece::None,
                     )
                     .unwrap_or_else(|err| {
                         println!("{}", err);
@@ -468,8 +706,7 @@ impl BoardState {
                         Piece::Queen,
                         *color,
                         self.get_captured_piece(BOARD_SQUARES[least_sign_bit as usize]),
-                        false,
-                        // later implement promotion
+                        Piece::None,
                     )
                     .unwrap_or_else(|err| {
                         println!("{}", err);
@@ -484,6 +721,97 @@ impl BoardState {
         moves_vec
     }
 
+    // the king goes two files towards a rook that still has its castling right. the squares
+    // between them are empty and the king doesn't start in, cross or land on an attacked square
+    fn generate_castling_moves(&self, color: Color) -> Vec<EncodedMove> {
+        let king_square: u32 = match color {
+            Color::White => 60,
+            Color::Black => 4,
+        };
+
+        if self.get_piece_bb(color, Piece::King) != BOARD_SQUARES[king_square as usize]
+            || self.is_square_attacked(king_square, opposite_color(color))
+        {
+            return vec![];
+        }
+
+        // (side, rook square, squares that must be empty, squares the king walks over)
+        let sides: [(usize, u32, &[i32], [i32; 2]); 2] = [
+            (0, king_square + 3, &[1, 2], [1, 2]),
+            (1, king_square - 4, &[-1, -2, -3], [-1, -2]),
+        ];
+
+        sides
+            .iter()
+            .filter(|(side, rook_square, empty, walked)| {
+                self.bb_castling_rigths[color as usize][*side] != 0
+                    && self.get_piece_bb(color, Piece::Rook) & BOARD_SQUARES[*rook_square as usize]
+                        != 0
+                    && empty.iter().all(|&offset| {
+                        self.bb_fullboard & BOARD_SQUARES[(king_square as i32 + offset) as usize]
+                            == 0
+                    })
+                    && walked.iter().all(|&offset| {
+                        !self.is_square_attacked(
+                            (king_square as i32 + offset) as u32,
+                            opposite_color(color),
+                        )
+                    })
+            })
+            .map(|(_, _, _, walked)| {
+                self.encode_move(
+                    king_square as u8,
+                    (king_square as i32 + walked[1]) as u8,
+                    Piece::King,
+                    color,
+                    Piece::None,
+                    Piece::None,
+                )
+                .unwrap_or_else(|err| {
+                    println!("{}", err);
+                    exit(1);
+                })
+            })
+            .collect()
+    }
+
+    // moves of the side to move that don't leave its own king in check
+    pub fn generate_legal_moves(&mut self) -> Vec<EncodedMove> {
+        let color = self.to_move;
+
+        self.generate_moves_by_color(&color)
+            .into_iter()
+            .filter(|&piece_move| {
+                self.make_move(piece_move);
+                let legal = !self.is_in_check(&color);
+                let _ = self.undo_move();
+
+                legal
+            })
+            .collect()
+    }
+
+    // totals are recomputed here and updated by every move afterwards
+    pub fn set_psq_tables(&mut self, psq_tables: Arc<PieceSquareTables>) {
+        (self.material, self.piece_square) = psq_tables.totals(self);
+        self.psq_tables = psq_tables;
+    }
+
+    // adds (sign 1) or removes (sign -1) a piece from the material and piece square totals
+    fn update_psq(&mut self, color: Color, piece: Piece, square: usize, sign: i32) {
+        let (material, piece_square) = self.psq_tables.get(color, piece as usize, square);
+
+        self.material[color as usize].0 += material.0 * sign;
+        self.material[color as usize].1 += material.1 * sign;
+        self.piece_square[color as usize].0 += piece_square.0 * sign;
+        self.piece_square[color as usize].1 += piece_square.1 * sign;
+    }
+
+    // accumulator is built from scratch here and updated by every move afterwards
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network.map(|network| NnueState::new(network, self));
+    }
+
     pub fn get_piece_bb(&self, color: Color, piece: Piece) -> Bitboard {
         self.bb_pieces[color as usize][piece as usize]
     }
@@ -492,7 +820,76 @@ impl BoardState {
         self.bb_colors[color as usize]
     }
 
-    pub fn from_fen(fen_string: &str) -> Result<BoardState, &str> {
+    // the board only flips halfmove between 0 and 1 on every move, it isn't a real fifty
+    // move clock, so the fen always gets 0 instead of a count other programs would misread
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+
+        for row in 0..8 {
+            let mut empty = 0;
+
+            for col in 0..8 {
+                let bb_square = BOARD_SQUARES[row * 8 + col];
+                let piece = (0..2).find_map(|color| {
+                    (0..6)
+                        .find(|&piece| self.bb_pieces[color][piece] & bb_square != 0)
+                        .map(|piece| (color, piece))
+                });
+
+                match piece {
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            placement += &empty.to_string();
+                            empty = 0;
+                        }
+
+                        let letter = b"PNBRQK"[piece] as char;
+
+                        placement.push(match color {
+                            0 => letter,
+                            _ => letter.to_ascii_lowercase(),
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                placement += &empty.to_string();
+            }
+
+            if row < 7 {
+                placement.push('/');
+            }
+        }
+
+        let to_move = match self.to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        let castling: String = [(0, 0, 'K'), (0, 1, 'Q'), (1, 0, 'k'), (1, 1, 'q')]
+            .iter()
+            .filter(|&&(color, side, _)| self.bb_castling_rigths[color][side] != 0)
+            .map(|&(_, _, letter)| letter)
+            .collect();
+
+        let en_passant = match self.bb_en_passant {
+            0 => "-".to_string(),
+            bb => square_to_string(bb.trailing_zeros()),
+        };
+
+        format!(
+            "{} {} {} {} 0 {}",
+            placement,
+            to_move,
+            if castling.is_empty() { "-" } else { &castling },
+            en_passant,
+            self.fullmove
+        )
+    }
+
+    pub fn from_fen(fen_string: &str) -> Result<BoardState, &'static str> {
         let fen: Vec<&str> = fen_string.split_whitespace().collect();
 
         if fen.len() != 6 {
@@ -593,8 +990,8 @@ impl BoardState {
             match char {
                 'K' => bb_castling_white_kingside = BOARD_SQUARES[63],
                 'Q' => bb_castling_white_queenside = BOARD_SQUARES[56],
-                'k' => bb_castling_black_kingside = BOARD_SQUARES[0],
-                'q' => bb_castling_black_queenside = BOARD_SQUARES[7],
+                'k' => bb_castling_black_kingside = BOARD_SQUARES[7],
+                'q' => bb_castling_black_queenside = BOARD_SQUARES[0],
                 _ => {
                     if char != '-' {
                         return Err("Incorrect castlings rights in fen string!");
@@ -655,7 +1052,7 @@ impl BoardState {
             Err(_) => return Err("Incorrect halfmove count in a fen string!"),
         };
 
-        Ok(BoardState {
+        let mut board = BoardState {
             bb_pieces: [
                 [
                     bb_white_pawns,
@@ -709,6 +1106,19 @@ impl BoardState {
             halfmove,
             fullmove,
             move_history: vec![],
-        })
+            key: 0,
+            pawn_key: 0,
+            history: vec![],
+            material: [(0, 0); 2],
+            piece_square: [(0, 0); 2],
+            psq_tables: Arc::new(PieceSquareTables::new(&EvalParams::default())),
+            nnue: None,
+        };
+
+        board.key = compute_key(&board);
+        board.pawn_key = compute_pawn_key(&board);
+        (board.material, board.piece_square) = board.psq_tables.totals(&board);
+
+        Ok(board)
     }
 }
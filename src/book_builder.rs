@@ -0,0 +1,156 @@
+use crate::{
+    board::Color,
+    pgn::{load_pgn, PgnGame},
+    polyglot::{encode_book_move, polyglot_key, Book, BookEntry},
+    san::parse_san,
+};
+use std::{cmp::Reverse, collections::BTreeMap, process::exit};
+
+pub const DEFAULT_BOOK_OUTPUT: &str = "book.bin";
+pub const DEFAULT_BOOK_PLIES: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct BookFilter {
+    // only the first plies of every game go in the book
+    pub max_plies: usize,
+    // moves played fewer times are left out
+    pub min_count: u32,
+    // only games with this result, like 1-0, all finished games without it
+    pub result: Option<String>,
+}
+
+impl Default for BookFilter {
+    fn default() -> BookFilter {
+        BookFilter {
+            max_plies: DEFAULT_BOOK_PLIES,
+            min_count: 1,
+            result: None,
+        }
+    }
+}
+
+// wins, draws and losses for the side playing the move
+pub type MoveStats = [u32; 3];
+
+#[derive(Debug, Default)]
+pub struct BookStats {
+    pub games: usize,
+    // games stopped at a move the board can't play, their earlier moves are kept
+    pub cut_short: usize,
+    pub moves: BTreeMap<(u64, u16), MoveStats>,
+}
+
+// index into the move stats of the game result for the side to move
+fn outcome(result: &str, color: Color) -> Option<usize> {
+    match (result, color) {
+        ("1-0", Color::White) | ("0-1", Color::Black) => Some(0),
+        ("1/2-1/2", _) => Some(1),
+        ("1-0", Color::Black) | ("0-1", Color::White) => Some(2),
+        _ => None,
+    }
+}
+
+pub fn collect_book_moves(games: &[PgnGame], filter: &BookFilter) -> BookStats {
+    let mut stats = BookStats::default();
+
+    for game in games {
+        if filter
+            .result
+            .as_ref()
+            .is_some_and(|result| *result != game.result)
+        {
+            continue;
+        }
+
+        // unfinished games say nothing about the moves
+        if outcome(&game.result, Color::White).is_none() {
+            continue;
+        }
+
+        stats.games += 1;
+
+        let Ok(mut board) = game.start_board() else {
+            stats.cut_short += 1;
+            continue;
+        };
+
+        for pgn_move in game.moves.iter().take(filter.max_plies) {
+            let Ok(piece_move) = parse_san(&mut board, &pgn_move.san) else {
+                stats.cut_short += 1;
+                break;
+            };
+
+            if let Some(index) = outcome(&game.result, board.to_move) {
+                stats
+                    .moves
+                    .entry((polyglot_key(&board), encode_book_move(piece_move)))
+                    .or_default()[index] += 1;
+            }
+
+            board.make_move(piece_move);
+        }
+    }
+
+    stats
+}
+
+// polyglot weights, a win counts twice as much as a draw and moves that never scored are
+// dropped. weights of a position are scaled down together when they don't fit in u16
+pub fn build_book(stats: &BookStats, filter: &BookFilter) -> Book {
+    let scores: Vec<(u64, u16, u32)> = stats
+        .moves
+        .iter()
+        .filter(|(_, [wins, draws, losses])| wins + draws + losses >= filter.min_count)
+        .map(|(&(key, raw_move), [wins, draws, _])| (key, raw_move, 2 * wins + draws))
+        .filter(|(_, _, score)| *score > 0)
+        .collect();
+
+    let mut entries = vec![];
+
+    // the moves map is sorted by key, so every position is one chunk
+    for position in scores.chunk_by(|(key, _, _), (other_key, _, _)| key == other_key) {
+        let max_score = position
+            .iter()
+            .map(|(_, _, score)| *score)
+            .max()
+            .unwrap_or(0);
+        let scale = max_score.div_ceil(u16::MAX as u32).max(1);
+
+        let mut moves: Vec<BookEntry> = position
+            .iter()
+            .map(|&(key, raw_move, score)| BookEntry {
+                key,
+                raw_move,
+                weight: (score / scale).max(1) as u16,
+                learn: 0,
+            })
+            .collect();
+
+        moves.sort_by_key(|entry| Reverse(entry.weight));
+        entries.extend(moves);
+    }
+
+    Book { entries }
+}
+
+pub fn make_book(path: &str, output: &str, filter: &BookFilter) {
+    let games = load_pgn(path).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+
+    let stats = collect_book_moves(&games, filter);
+    let book = build_book(&stats, filter);
+
+    book.save(output).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+
+    println!(
+        "games: {}, cut short: {}, entries: {}",
+        stats.games,
+        stats.cut_short,
+        book.entries.len()
+    );
+}
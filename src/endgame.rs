@@ -0,0 +1,408 @@
+use crate::{
+    board::{BoardState, Color, Piece},
+    eval::{CHECKMATE, DRAW, ENDGAME_PAWN, ENDGAME_QUEEN, ENDGAME_ROOK},
+    kpk::kpk_probe,
+    pawn_structure::relative_rank,
+    piece_parsing::parse_bitboards,
+    piece_terms::LIGHT_SQUARES,
+    utils::opposite_color,
+};
+
+// these are deliberately fixed and not in EvalParams. the tuner fits the traced terms only,
+// the endgame scores and scale factors are applied after them so it couldn't learn these,
+// and KNOWN_WIN only has to stay above the general eval and below the mate scores
+//
+// a won ending the search can't see the mate of yet, above anything the general eval gives
+pub const KNOWN_WIN: i32 = 2_000;
+// endgame scores stay far away from the mate scores
+pub const MAX_ENDGAME_SCORE: i32 = CHECKMATE / 2;
+// scale factors are out of SCALE_NORMAL
+pub const SCALE_NORMAL: i32 = 64;
+pub const OPPOSITE_BISHOPS_SCALE: i32 = 32;
+pub const PAWNLESS_SCALE: i32 = 8;
+
+// pieces of every kind by color, indexed by the piece
+pub type MaterialCounts = [[u32; 6]; 2];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EndgameResult {
+    // exact score for white, the general terms aren't looked at
+    Score(i32),
+    // the general score is scaled by factor / SCALE_NORMAL
+    Scale(i32),
+}
+
+pub struct Endgame {
+    pub name: &'static str,
+    // material signature with the given color as the strong side
+    pub matches: fn(&MaterialCounts, Color) -> bool,
+    // score for the strong side
+    pub evaluate: fn(&BoardState, Color) -> EndgameResult,
+}
+
+// tried in order for both colors as the strong side, the first match wins
+pub const ENDGAMES: [Endgame; 8] = [
+    Endgame {
+        name: "KmK",
+        matches: is_kmk,
+        evaluate: |_, _| EndgameResult::Score(DRAW),
+    },
+    Endgame {
+        name: "KBNK",
+        matches: is_kbnk,
+        evaluate: kbnk,
+    },
+    Endgame {
+        name: "KXK",
+        matches: is_kxk,
+        evaluate: kxk,
+    },
+    Endgame {
+        name: "KPK",
+        matches: is_kpk,
+        evaluate: kpk,
+    },
+    Endgame {
+        name: "KRKP",
+        matches: is_krkp,
+        evaluate: krkp,
+    },
+    Endgame {
+        name: "KQKP",
+        matches: is_kqkp,
+        evaluate: kqkp,
+    },
+    Endgame {
+        name: "KBKB",
+        matches: is_opposite_bishops,
+        evaluate: opposite_bishops,
+    },
+    Endgame {
+        name: "pawnless",
+        matches: is_drawish_pawnless,
+        evaluate: |_, _| EndgameResult::Scale(PAWNLESS_SCALE),
+    },
+];
+
+pub fn material_counts(board: &BoardState) -> MaterialCounts {
+    let mut counts = [[0; 6]; 2];
+
+    for color in [Color::White, Color::Black] {
+        for (index, piece) in [
+            Piece::Pawn,
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Rook,
+            Piece::Queen,
+            Piece::King,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            counts[color as usize][index] = board.get_piece_bb(color, piece).count_ones();
+        }
+    }
+
+    counts
+}
+
+// first endgame of the registry matching the material on the board, score for white
+pub fn probe_endgame(board: &BoardState) -> Option<(&'static str, EndgameResult)> {
+    let counts = material_counts(board);
+
+    for endgame in ENDGAMES.iter() {
+        for strong in [Color::White, Color::Black] {
+            if !(endgame.matches)(&counts, strong) {
+                continue;
+            }
+
+            let result = match ((endgame.evaluate)(board, strong), strong) {
+                (EndgameResult::Score(score), Color::Black) => EndgameResult::Score(-score),
+                (result, _) => result,
+            };
+
+            return Some((endgame.name, result));
+        }
+    }
+
+    None
+}
+
+fn non_pawn_count(counts: &MaterialCounts, color: Color) -> u32 {
+    counts[color as usize][1..5].iter().sum()
+}
+
+fn is_bare_king(counts: &MaterialCounts, color: Color) -> bool {
+    counts[color as usize][..5].iter().all(|&count| count == 0)
+}
+
+fn only_has(counts: &MaterialCounts, color: Color, piece: Piece, count: u32) -> bool {
+    counts[color as usize][..5]
+        .iter()
+        .enumerate()
+        .all(|(index, &piece_count)| match index == piece as usize {
+            true => piece_count == count,
+            false => piece_count == 0,
+        })
+}
+
+fn square_of(board: &BoardState, color: Color, piece: Piece) -> usize {
+    parse_bitboards(color, board.get_piece_bb(color, piece))
+        .first()
+        .map(|square| square.1 as usize)
+        .unwrap_or(0)
+}
+
+pub fn distance(square: usize, other_square: usize) -> i32 {
+    let rows = (square / 8).abs_diff(other_square / 8);
+    let columns = (square % 8).abs_diff(other_square % 8);
+
+    rows.max(columns) as i32
+}
+
+// 0 in the center up to 6 in the corners
+fn edge_distance(square: usize) -> i32 {
+    let (row, column) = (square / 8, square % 8);
+
+    6 - (row.min(7 - row) + column.min(7 - column)) as i32
+}
+
+fn push_to_edge(square: usize) -> i32 {
+    20 * edge_distance(square)
+}
+
+fn push_close(square: usize, other_square: usize) -> i32 {
+    20 * (7 - distance(square, other_square))
+}
+
+// lone king against a single minor or two knights can't be mated
+fn is_kmk(counts: &MaterialCounts, strong: Color) -> bool {
+    let own = &counts[strong as usize];
+
+    is_bare_king(counts, opposite_color(strong))
+        && own[Piece::Pawn as usize] == 0
+        && own[Piece::Rook as usize] == 0
+        && own[Piece::Queen as usize] == 0
+        && (own[Piece::Knight as usize] + own[Piece::Bishop as usize] <= 1
+            || only_has(counts, strong, Piece::Knight, 2))
+}
+
+fn is_kbnk(counts: &MaterialCounts, strong: Color) -> bool {
+    let own = &counts[strong as usize];
+
+    is_bare_king(counts, opposite_color(strong))
+        && own[Piece::Knight as usize] == 1
+        && own[Piece::Bishop as usize] == 1
+        && non_pawn_count(counts, strong) == 2
+        && own[Piece::Pawn as usize] == 0
+}
+
+// anything with mating material against a lone king
+fn is_kxk(counts: &MaterialCounts, strong: Color) -> bool {
+    let own = &counts[strong as usize];
+
+    is_bare_king(counts, opposite_color(strong))
+        && (own[Piece::Queen as usize] > 0
+            || own[Piece::Rook as usize] > 0
+            || own[Piece::Bishop as usize] + own[Piece::Knight as usize] >= 2)
+}
+
+// the weak king goes to the edge and the strong king follows it. bishops all on one color
+// can't mate, without pawns that's a draw and with them it's left to the general eval
+fn kxk(board: &BoardState, strong: Color) -> EndgameResult {
+    let weak = opposite_color(strong);
+    let bb_bishops = board.get_piece_bb(strong, Piece::Bishop);
+    let bb_mating = board.get_piece_bb(strong, Piece::Knight)
+        | board.get_piece_bb(strong, Piece::Rook)
+        | board.get_piece_bb(strong, Piece::Queen);
+
+    if bb_mating == 0 && (bb_bishops & LIGHT_SQUARES == 0 || bb_bishops & !LIGHT_SQUARES == 0) {
+        return match board.get_piece_bb(strong, Piece::Pawn) {
+            0 => EndgameResult::Score(DRAW),
+            _ => EndgameResult::Scale(SCALE_NORMAL),
+        };
+    }
+
+    let strong_king = square_of(board, strong, Piece::King);
+    let weak_king = square_of(board, weak, Piece::King);
+
+    let score = board.material[strong as usize].1
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king)
+        + KNOWN_WIN;
+
+    EndgameResult::Score(score.min(MAX_ENDGAME_SCORE))
+}
+
+// the mate only works in a corner of the bishop's color
+fn kbnk(board: &BoardState, strong: Color) -> EndgameResult {
+    let weak = opposite_color(strong);
+    let strong_king = square_of(board, strong, Piece::King);
+    let weak_king = square_of(board, weak, Piece::King);
+    let bishop = 1 << square_of(board, strong, Piece::Bishop);
+
+    // a8 and h1 are light, h8 and a1 dark
+    let corners = match bishop & LIGHT_SQUARES != 0 {
+        true => [0, 63],
+        false => [7, 56],
+    };
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| distance(weak_king, corner))
+        .min()
+        .unwrap_or(0);
+
+    let score = board.material[strong as usize].1
+        + KNOWN_WIN
+        + push_close(strong_king, weak_king)
+        + 40 * (7 - corner_distance);
+
+    EndgameResult::Score(score)
+}
+
+fn is_kpk(counts: &MaterialCounts, strong: Color) -> bool {
+    is_bare_king(counts, opposite_color(strong)) && only_has(counts, strong, Piece::Pawn, 1)
+}
+
+// exact from the bitbase, a win gets better the further the pawn is
+fn kpk_value(board: &BoardState, strong: Color) -> i32 {
+    let weak = opposite_color(strong);
+    let pawn = square_of(board, strong, Piece::Pawn);
+
+    // the bitbase has white as the strong side, flipping the row mirrors the board
+    let flip = match strong {
+        Color::White => 0,
+        Color::Black => 56,
+    };
+    let to_move = match board.to_move as usize == strong as usize {
+        true => Color::White,
+        false => Color::Black,
+    };
+
+    match kpk_probe(
+        square_of(board, strong, Piece::King) ^ flip,
+        pawn ^ flip,
+        square_of(board, weak, Piece::King) ^ flip,
+        to_move,
+    ) {
+        true => KNOWN_WIN + ENDGAME_PAWN + 20 * relative_rank(strong, pawn) as i32,
+        false => DRAW,
+    }
+}
+
+fn kpk(board: &BoardState, strong: Color) -> EndgameResult {
+    EndgameResult::Score(kpk_value(board, strong))
+}
+
+// exact kpk score for the side to move, for the search to stop at
+pub fn kpk_score(board: &BoardState) -> Option<i32> {
+    let counts = material_counts(board);
+
+    [Color::White, Color::Black]
+        .into_iter()
+        .find(|&strong| is_kpk(&counts, strong))
+        .map(|strong| match board.to_move as usize == strong as usize {
+            true => kpk_value(board, strong),
+            false => -kpk_value(board, strong),
+        })
+}
+
+fn is_krkp(counts: &MaterialCounts, strong: Color) -> bool {
+    only_has(counts, strong, Piece::Rook, 1)
+        && only_has(counts, opposite_color(strong), Piece::Pawn, 1)
+}
+
+// won when the strong king gets in front of the pawn or the weak king is too far away to
+// support it, close to a draw when the pawn is far advanced with its king next to it
+fn krkp(board: &BoardState, strong: Color) -> EndgameResult {
+    let weak = opposite_color(strong);
+    let strong_king = square_of(board, strong, Piece::King);
+    let weak_king = square_of(board, weak, Piece::King);
+    let rook = square_of(board, strong, Piece::Rook);
+    let pawn = square_of(board, weak, Piece::Pawn);
+
+    let promotion_square = match weak {
+        Color::White => pawn % 8,
+        Color::Black => 56 + pawn % 8,
+    };
+    let pawn_rank = relative_rank(weak, pawn) as i32;
+    let tempo = (board.to_move as usize == weak as usize) as i32;
+
+    let in_front =
+        strong_king % 8 == pawn % 8 && relative_rank(weak, strong_king) as i32 > pawn_rank;
+
+    let score =
+        if in_front || (distance(weak_king, pawn) - tempo >= 3 && distance(weak_king, rook) >= 3) {
+            ENDGAME_ROOK - distance(strong_king, pawn) * 10
+        } else if pawn_rank >= 5
+            && distance(weak_king, pawn) == 1
+            && distance(strong_king, pawn) - (1 - tempo) >= 3
+        {
+            80 - distance(strong_king, pawn) * 8
+        } else {
+            200 - 8 * (distance(strong_king, promotion_square) - distance(weak_king, pawn))
+        };
+
+    EndgameResult::Score(score)
+}
+
+fn is_kqkp(counts: &MaterialCounts, strong: Color) -> bool {
+    only_has(counts, strong, Piece::Queen, 1)
+        && only_has(counts, opposite_color(strong), Piece::Pawn, 1)
+}
+
+// a rook or bishop pawn on the seventh with its king next to it holds the draw
+fn kqkp(board: &BoardState, strong: Color) -> EndgameResult {
+    let weak = opposite_color(strong);
+    let strong_king = square_of(board, strong, Piece::King);
+    let weak_king = square_of(board, weak, Piece::King);
+    let pawn = square_of(board, weak, Piece::Pawn);
+
+    let drawing_file = [0, 2, 5, 7].contains(&(pawn % 8));
+    let mut score = push_close(strong_king, weak_king);
+
+    if relative_rank(weak, pawn) != 6 || distance(weak_king, pawn) != 1 || !drawing_file {
+        score += ENDGAME_QUEEN - ENDGAME_PAWN + KNOWN_WIN / 2;
+    }
+
+    EndgameResult::Score(score)
+}
+
+// one bishop each on differently colored squares and only pawns besides
+fn is_opposite_bishops(counts: &MaterialCounts, strong: Color) -> bool {
+    matches!(strong, Color::White)
+        && [Color::White, Color::Black].iter().all(|&color| {
+            counts[color as usize][Piece::Bishop as usize] == 1
+                && non_pawn_count(counts, color) == 1
+        })
+}
+
+fn opposite_bishops(board: &BoardState, _: Color) -> EndgameResult {
+    let light_bishops = [Color::White, Color::Black]
+        .iter()
+        .filter(|&&color| board.get_piece_bb(color, Piece::Bishop) & LIGHT_SQUARES != 0)
+        .count();
+
+    match light_bishops {
+        1 => EndgameResult::Scale(OPPOSITE_BISHOPS_SCALE),
+        _ => EndgameResult::Scale(SCALE_NORMAL),
+    }
+}
+
+// without pawns less than a minor piece up doesn't win, like rook against rook
+fn is_drawish_pawnless(counts: &MaterialCounts, strong: Color) -> bool {
+    // rough piece values, a minor piece is 3
+    const VALUES: [u32; 5] = [0, 3, 3, 5, 9];
+
+    let value = |color: Color| -> u32 {
+        counts[color as usize][..5]
+            .iter()
+            .zip(VALUES)
+            .map(|(count, value)| count * value)
+            .sum()
+    };
+
+    matches!(strong, Color::White)
+        && counts[0][Piece::Pawn as usize] + counts[1][Piece::Pawn as usize] == 0
+        && value(Color::White).abs_diff(value(Color::Black)) < 3
+}
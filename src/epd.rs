@@ -0,0 +1,293 @@
+use crate::{
+    board::{BoardState, EncodedMove},
+    options::EngineOptions,
+    san::{move_to_san, parse_san},
+    search::SearchLimits,
+    smp::lazy_smp,
+    tt::TranspositionTable,
+    utils::move_to_string,
+};
+use std::{
+    fs,
+    process::exit,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+pub const DEFAULT_EPD_MOVETIME: u64 = 1000;
+
+// one position per line, the first four fen fields and then operations ending with ;
+// like bm Nf3 Qd4; am e4; id "WAC.001"; c0 "comment";
+#[derive(Debug, Clone)]
+pub struct EpdPosition {
+    pub board: BoardState,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EpdOutcome {
+    Pass,
+    Fail,
+    // no bm or am to check
+    Skip,
+}
+
+// operands split on whitespace, strings in quotes are kept whole
+fn parse_operands(text: &str) -> Result<Vec<String>, &'static str> {
+    let mut operands = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+
+            let operand: String = chars.by_ref().take_while(|&c| c != '"').collect();
+
+            operands.push(operand);
+        } else {
+            let mut operand = String::new();
+
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+
+                operand.push(c);
+                chars.next();
+            }
+
+            operands.push(operand);
+        }
+    }
+
+    if !text.matches('"').count().is_multiple_of(2) {
+        return Err("Unclosed string in an EPD operation!");
+    }
+
+    Ok(operands)
+}
+
+pub fn parse_epd_line(line: &str) -> Result<EpdPosition, &'static str> {
+    let fields: Vec<&str> = line.split_whitespace().take(5).collect();
+
+    if fields.len() < 4 {
+        return Err("Incorrect EPD position!");
+    }
+
+    // opcodes start with a letter, a number here is the halfmove clock of a full fen
+    if fields.len() == 5 && fields[4].parse::<u32>().is_ok() {
+        return Err("EPD positions have four FEN fields, move counters go in hmvc and fmvn!");
+    }
+
+    let fields = &fields[..4];
+
+    // the board keeps only the parity of the halfmove clock, so the counters are reset
+    let fullmove = line_operation(line, "fmvn").unwrap_or("1".to_string());
+    let board = BoardState::from_fen(&format!("{} 0 {}", fields.join(" "), fullmove))?;
+
+    let mut operations = vec![];
+    let mut rest = line.trim_start();
+
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..];
+    }
+
+    // semicolons inside strings don't end an operation
+    let mut start = 0;
+    let mut in_string = false;
+
+    for (index, c) in rest.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                let operation = rest[start..index].trim();
+                start = index + 1;
+
+                if operation.is_empty() {
+                    continue;
+                }
+
+                let (opcode, operands) = operation
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((operation, ""));
+
+                operations.push((opcode.to_string(), parse_operands(operands)?));
+            }
+            _ => (),
+        }
+    }
+
+    if !rest[start..].trim().is_empty() {
+        return Err("EPD operations must end with ;!");
+    }
+
+    Ok(EpdPosition { board, operations })
+}
+
+// first operand of an opcode straight from the line, before the board is set up
+fn line_operation(line: &str, opcode: &str) -> Option<String> {
+    line.split(';').find_map(|operation| {
+        let mut words = operation.split_whitespace();
+
+        (words.next() == Some(opcode))
+            .then(|| words.next().map(str::to_string))
+            .flatten()
+    })
+}
+
+pub fn load_epd(path: &str) -> Vec<EpdPosition> {
+    let content = fs::read_to_string(path).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            parse_epd_line(line).unwrap_or_else(|err| {
+                println!("{} ({})", err, line);
+                exit(1);
+            })
+        })
+        .collect()
+}
+
+impl EpdPosition {
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")
+            .and_then(|operands| operands.first())
+            .map(String::as_str)
+    }
+
+    // moves of an opcode like bm or am, in san or coordinate notation
+    pub fn moves(&self, opcode: &str) -> Result<Vec<EncodedMove>, &'static str> {
+        let mut board = self.board.clone();
+        let legal_moves = board.generate_legal_moves();
+
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|notation| {
+                parse_san(&mut board, notation).or_else(|err| {
+                    legal_moves
+                        .iter()
+                        .find(|&&piece_move| move_to_string(piece_move) == *notation)
+                        .copied()
+                        .ok_or(err)
+                })
+            })
+            .collect()
+    }
+
+    // whether there's a bm or an am to check moves against, an error when one of them
+    // can't be read
+    pub fn has_expectations(&self) -> Result<bool, &'static str> {
+        let best_moves = self.moves("bm")?;
+        let avoid_moves = self.moves("am")?;
+
+        Ok(!best_moves.is_empty() || !avoid_moves.is_empty())
+    }
+
+    // a move passes when it's one of the best moves and none of the moves to avoid
+    pub fn check(&self, piece_move: EncodedMove) -> Result<EpdOutcome, &'static str> {
+        let best_moves = self.moves("bm")?;
+        let avoid_moves = self.moves("am")?;
+
+        if best_moves.is_empty() && avoid_moves.is_empty() {
+            return Ok(EpdOutcome::Skip);
+        }
+
+        match (best_moves.is_empty() || best_moves.contains(&piece_move))
+            && !avoid_moves.contains(&piece_move)
+        {
+            true => Ok(EpdOutcome::Pass),
+            false => Ok(EpdOutcome::Fail),
+        }
+    }
+}
+
+// searches every position of a test suite with the same limits and prints the moves found
+// against the expected ones, then how many passed
+pub fn run_epd(path: &str, limits: SearchLimits, options: &EngineOptions) {
+    let positions = load_epd(path);
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut skipped = 0;
+
+    for (number, position) in positions.iter().enumerate() {
+        let id = position
+            .id()
+            .map(str::to_string)
+            .unwrap_or_else(|| (number + 1).to_string());
+
+        // positions without anything to check aren't searched
+        match position.has_expectations() {
+            Ok(true) => (),
+            Ok(false) => {
+                skipped += 1;
+                println!("{}: skipped, no bm or am", id);
+                continue;
+            }
+            Err(err) => {
+                skipped += 1;
+                println!("{}: skipped, {}", id, err);
+                continue;
+            }
+        }
+
+        // every position starts from an empty table so the results don't depend on the order
+        let tt = Arc::new(TranspositionTable::new(options.hash_mb));
+        let result = lazy_smp(
+            &position.board,
+            limits,
+            options,
+            tt,
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        let Some(best_move) = result.best_move else {
+            skipped += 1;
+            println!("{}: skipped, no legal moves", id);
+            continue;
+        };
+
+        let found = move_to_san(&mut position.board.clone(), best_move);
+        let expected: Vec<String> = ["bm", "am"]
+            .iter()
+            .filter_map(|&opcode| {
+                position
+                    .operation(opcode)
+                    .map(|operands| format!("{} {}", opcode, operands.join(" ")))
+            })
+            .collect();
+
+        match position.check(best_move) {
+            Ok(EpdOutcome::Pass) => {
+                passed += 1;
+                println!("{}: pass, found {} ({})", id, found, expected.join(", "));
+            }
+            _ => {
+                failed += 1;
+                println!("{}: fail, found {} ({})", id, found, expected.join(", "));
+            }
+        }
+    }
+
+    println!(
+        "passed: {}/{}, failed: {}, skipped: {}",
+        passed,
+        passed + failed,
+        failed,
+        skipped
+    );
+}
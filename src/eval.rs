@@ -3,8 +3,14 @@
 
 use crate::{
     board::{BoardState, Color, Piece},
-    move_generation::generate_king_moves,
+    endgame::{probe_endgame, EndgameResult, SCALE_NORMAL},
+    eval_params::EvalParams,
+    king_safety::king_safety_score,
+    mobility::mobility_score,
+    pawn_hash::PawnHashTable,
+    pawn_structure::passed_pawn_score,
     piece_parsing::parse_bitboards,
+    piece_terms::piece_terms_score,
 };
 
 pub const DRAW: i32 = 0;
@@ -26,80 +32,53 @@ pub const ENDGAME_KING: i32 = 20_000;
 
 // bonus for both black and white pawns in both opening (and middlegame) and endgame
 pub const WHITE_PAWN_MIDDLEGAME_BONUS: [i32; 64] = [
-    0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, -20, -20, 10, 10, 5, 5, -5, -10, 0, 0, -10, -5, 5, 0, 0, 0,
-    20, 20, 0, 0, 0, 5, 5, 10, 25, 25, 10, 5, 5, 10, 10, 20, 30, 30, 20, 10, 10, 50, 50, 50, 50,
-    50, 50, 50, 50, 0, 0, 0, 0, 0, 0, 0, 0,
-];
-
-pub const WHITE_PAWN_ENDGAME_BONUS: [i32; 64] = [
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, -20, -20, 10, 10, 5, 5, -5, -10, 0,
-    0, -10, -5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, 5, 10, 25, 25, 10, 5, 5, 10, 10, 20, 30, 30, 20,
-    10, 10, 50, 50, 50, 50, 50, 50, 50, 50,
-];
-
-pub const BLACK_PAWN_MIDDLEGAME_BONUS: [i32; 64] = [
     0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5,
     10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20,
     -20, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
 
-pub const BLACK_PAWN_ENDGAME_BONUS: [i32; 64] = [
+pub const WHITE_PAWN_ENDGAME_BONUS: [i32; 64] = [
     50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5, 10, 25, 25, 10, 5, 5, 0,
     0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20, -20, 10, 10, 5, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
 
 // bonus for both white and black knights
-pub const WHITE_KNIGHT_BONUS: [i32; 64] = [
-    -50, -40, -30, -30, -30, -30, -40, -50, -40, 20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15, 10,
+pub const WHITE_KNIGHT_MIDDLEGAME_BONUS: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15, 10,
     0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 10, 15, 15, 10,
     5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
 ];
 
-pub const BLACK_KNIGHT_BONUS: [i32; 64] = [
-    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 5, 5, 0, -20, -40, -30, 5, 10, 15, 15, 10,
-    5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 10, 15, 15, 10,
-    0, -30, -40, -20, 0, 0, 0, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
-];
+// minor and major pieces use the middlegame tables in the endgame until they're tuned apart
+pub const WHITE_KNIGHT_ENDGAME_BONUS: [i32; 64] = WHITE_KNIGHT_MIDDLEGAME_BONUS;
 
 // bonus for both white and black bishops
-pub const WHITE_BISHOP_BONUS: [i32; 64] = [
+pub const WHITE_BISHOP_MIDDLEGAME_BONUS: [i32; 64] = [
     -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10, 5, 0,
     -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10, 10, 10, 10, 10,
     -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10, -20,
 ];
 
-pub const BLACK_BISHOP_BONUS: [i32; 64] = [
-    -20, -10, -10, -10, -10, -10, -10, -20, -10, 5, 0, 0, 0, 0, 5, -10, -10, 10, 10, 10, 10, 10,
-    10, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 5, 10, 10, 5, 0,
-    -10, -10, 0, 0, 0, 0, 0, 0, -10, -20, -10, -10, -10, -10, -10, -10, -20,
-];
+pub const WHITE_BISHOP_ENDGAME_BONUS: [i32; 64] = WHITE_BISHOP_MIDDLEGAME_BONUS;
 
 // bonus for both black and white rooks
-pub const WHITE_ROOK_BONUS: [i32; 64] = [
+pub const WHITE_ROOK_MIDDLEGAME_BONUS: [i32; 64] = [
     0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
     0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0,
     0, 5, 5, 0, 0, 0,
 ];
 
-pub const BLACK_ROOK_BONUS: [i32; 64] = [
-    0, 0, 0, 5, 5, 0, 0, 0, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0,
-    0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 5, 10, 10, 10, 10, 10, 10, 5, 0, 0,
-    0, 0, 0, 0, 0, 0,
-];
+pub const WHITE_ROOK_ENDGAME_BONUS: [i32; 64] = WHITE_ROOK_MIDDLEGAME_BONUS;
 
 // bonus for both black and white queens
-pub const WHITE_QUEEN_BONUS: [i32; 64] = [
+pub const WHITE_QUEEN_MIDDLEGAME_BONUS: [i32; 64] = [
     -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
     -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0,
     0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
 ];
 
-pub const BLACK_QUEEN_BONUS: [i32; 64] = [
-    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 5, 0, 0, -10, -10, 5, 5, 5, 5, 5, 0, -10,
-    0, 0, 5, 5, 5, 5, 0, -5, -5, 0, 5, 5, 5, 5, 0, -5, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0, 0, 0, 0,
-    0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
-];
+pub const WHITE_QUEEN_ENDGAME_BONUS: [i32; 64] = WHITE_QUEEN_MIDDLEGAME_BONUS;
 
 // bonus for both black and white kind in both opening ( middlegame ) and endgame
 pub const WHITE_KING_MIDDLEGAME_BONUS: [i32; 64] = [
@@ -116,401 +95,284 @@ pub const WHITE_KING_ENDGAME_BONUS: [i32; 64] = [
     -30, -50,
 ];
 
-pub const BLACK_KING_MIDDLEGAME_BONUS: [i32; 64] = [
-    20, 30, 10, 0, 0, 10, 30, 20, 20, 20, 0, 0, 0, 0, 20, 20, -10, -20, -20, -20, -20, -20, -20,
-    -10, -20, -30, -30, -40, -40, -30, -30, -20, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40,
-    -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50,
-    -40, -40, -30,
-];
-
-pub const BLACK_KING_ENDGAME_BONUS: [i32; 64] = [
-    -50, -30, -30, -30, -30, -30, -30, -50, -30, -30, 0, 0, 0, 0, -30, -30, -30, -10, 20, 30, 30,
-    20, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10,
-    20, 30, 30, 20, -10, -30, -30, -20, -10, 0, 0, -10, -20, -30, -50, -40, -30, -20, -20, -30,
-    -40, -50,
-];
-
-pub fn evaluate(board: &BoardState) -> i32 {
-    // for negamax, relative eval
-    let color_const = match board.to_move {
-        Color::White => 1,
-        Color::Black => -1,
-    };
-
-    // if amount of pieces on the board except pawn are less that 4 + 2 kings
-    if ((board.bb_fullboard ^ board.get_piece_bb(Color::White, Piece::Pawn))
-        ^ board.get_piece_bb(Color::Black, Piece::Pawn))
-    .count_ones()
-        < 6
-    {
-        // engame
-        // white pawns
-        let white_pawn_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Pawn));
-
-        let mut white_pawn_score = 0;
-
-        for pos in white_pawn_position.iter() {
-            white_pawn_score += ENDGAME_PAWN + WHITE_PAWN_ENDGAME_BONUS[pos.1 as usize];
-        }
+// game phase weights, TOTAL_PHASE with all pieces on the board and 0 with only kings and pawns
+pub const KNIGHT_PHASE: i32 = 1;
+pub const BISHOP_PHASE: i32 = 1;
+pub const ROOK_PHASE: i32 = 2;
+pub const QUEEN_PHASE: i32 = 4;
+pub const TOTAL_PHASE: i32 = 24;
 
-        // white knights
-        let white_knight_position = parse_bitboards(
-            Color::White,
-            board.get_piece_bb(Color::White, Piece::Knight),
-        );
+// (middlegame, endgame)
+pub const BISHOP_PAIR_BONUS: (i32, i32) = (50, 50);
 
-        let mut white_knight_score = 0;
+pub fn game_phase(board: &BoardState) -> i32 {
+    let mut phase = 0;
 
-        for pos in white_knight_position.iter() {
-            white_knight_score += ENDGAME_KNIGHT + WHITE_KNIGHT_BONUS[pos.1 as usize];
-        }
+    for color in [Color::White, Color::Black] {
+        phase += board.get_piece_bb(color, Piece::Knight).count_ones() as i32 * KNIGHT_PHASE
+            + board.get_piece_bb(color, Piece::Bishop).count_ones() as i32 * BISHOP_PHASE
+            + board.get_piece_bb(color, Piece::Rook).count_ones() as i32 * ROOK_PHASE
+            + board.get_piece_bb(color, Piece::Queen).count_ones() as i32 * QUEEN_PHASE;
+    }
 
-        // white bishops
-        let white_bishop_position = parse_bitboards(
-            Color::White,
-            board.get_piece_bb(Color::White, Piece::Bishop),
-        );
+    // promotions can push it over the starting material
+    phase.min(TOTAL_PHASE)
+}
 
-        let mut white_bishop_score = 0;
+// linear blend between middlegame and endgame scores by game phase
+pub fn taper(middlegame: i32, endgame: i32, phase: i32) -> i32 {
+    (middlegame * phase + endgame * (TOTAL_PHASE - phase)) / TOTAL_PHASE
+}
 
-        for pos in white_bishop_position.iter() {
-            white_bishop_score += ENDGAME_BISHOP + WHITE_BISHOP_BONUS[pos.1 as usize];
-        }
+// (middlegame, endgame) by color
+pub type ColorScores = [(i32, i32); 2];
 
-        // white rooks
-        let white_rook_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Rook));
+// material and bonus tables looked up by color, piece and square, (middlegame, endgame).
+// built from the params once, the board keeps totals from them up to date on every move
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceSquareTables {
+    pub material: [(i32, i32); 6],
+    pub piece_square: [[[(i32, i32); 64]; 6]; 2],
+}
 
-        let mut white_rook_score = 0;
+impl PieceSquareTables {
+    pub fn new(params: &EvalParams) -> PieceSquareTables {
+        let mut tables = PieceSquareTables {
+            material: [(0, 0); 6],
+            piece_square: [[[(0, 0); 64]; 6]; 2],
+        };
 
-        for pos in white_rook_position.iter() {
-            white_rook_score += ENDGAME_ROOK + WHITE_ROOK_BONUS[pos.1 as usize];
+        // kings are always on the board so they don't count as material
+        for index in 0..5 {
+            tables.material[index] = (
+                params.middlegame_values[index],
+                params.endgame_values[index],
+            );
         }
 
-        // white queen
-        let white_queen_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Queen));
-
-        let mut white_queen_score = 0;
-
-        for pos in white_queen_position.iter() {
-            white_queen_score += ENDGAME_QUEEN + WHITE_QUEEN_BONUS[pos.1 as usize];
+        for index in 0..6 {
+            for square in 0..64 {
+                // tables are from the point of view of white, flipping the row mirrors them for black
+                for (color, flip) in [(Color::White, 0), (Color::Black, 56)] {
+                    tables.piece_square[color as usize][index][square] = (
+                        params.middlegame_tables[index][square ^ flip],
+                        params.endgame_tables[index][square ^ flip],
+                    );
+                }
+            }
         }
 
-        // white king
-        let white_king_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Queen));
-
-        let mut white_king_bonus = 0;
+        tables
+    }
 
-        for pos in white_king_position.iter() {
-            white_king_bonus += WHITE_KING_ENDGAME_BONUS[pos.1 as usize];
+    // (material, piece square) totals by color computed from scratch
+    pub fn totals(&self, board: &BoardState) -> (ColorScores, ColorScores) {
+        let mut material = [(0, 0); 2];
+        let mut piece_square = [(0, 0); 2];
+
+        for color in [Color::White, Color::Black] {
+            for index in 0..6 {
+                for pos in parse_bitboards(color, board.bb_pieces[color as usize][index]).iter() {
+                    let (material_score, piece_square_score) =
+                        self.get(color, index, pos.1 as usize);
+
+                    material[color as usize].0 += material_score.0;
+                    material[color as usize].1 += material_score.1;
+                    piece_square[color as usize].0 += piece_square_score.0;
+                    piece_square[color as usize].1 += piece_square_score.1;
+                }
+            }
         }
 
-        let black_pawn_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Pawn));
-
-        let mut black_pawn_score = 0;
+        (material, piece_square)
+    }
 
-        for pos in black_pawn_position.iter() {
-            black_pawn_score += ENDGAME_PAWN + BLACK_PAWN_ENDGAME_BONUS[pos.1 as usize];
-        }
+    // (material, piece square) of one piece
+    pub fn get(&self, color: Color, piece: usize, square: usize) -> ((i32, i32), (i32, i32)) {
+        (
+            self.material[piece],
+            self.piece_square[color as usize][piece][square],
+        )
+    }
+}
 
-        // black knights
-        let black_knight_position = parse_bitboards(
-            Color::Black,
-            board.get_piece_bb(Color::Black, Piece::Knight),
-        );
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EvalTerm {
+    Material = 0,
+    PieceSquare = 1,
+    BishopPair = 2,
+    Pawns = 3,
+    PassedPawns = 4,
+    Mobility = 5,
+    KingSafety = 6,
+    Pieces = 7,
+}
 
-        let mut black_knight_score = 0;
+pub const EVAL_TERMS: [EvalTerm; 8] = [
+    EvalTerm::Material,
+    EvalTerm::PieceSquare,
+    EvalTerm::BishopPair,
+    EvalTerm::Pawns,
+    EvalTerm::PassedPawns,
+    EvalTerm::Mobility,
+    EvalTerm::KingSafety,
+    EvalTerm::Pieces,
+];
 
-        for pos in black_knight_position.iter() {
-            black_knight_score += ENDGAME_KNIGHT + BLACK_KNIGHT_BONUS[pos.1 as usize];
+impl EvalTerm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvalTerm::Material => "material",
+            EvalTerm::PieceSquare => "piece square",
+            EvalTerm::BishopPair => "bishop pair",
+            EvalTerm::Pawns => "pawns",
+            EvalTerm::PassedPawns => "passed pawns",
+            EvalTerm::Mobility => "mobility",
+            EvalTerm::KingSafety => "king safety",
+            EvalTerm::Pieces => "pieces",
         }
+    }
+}
 
-        // black bishops
-        let black_bishop_position = parse_bitboards(
-            Color::Black,
-            board.get_piece_bb(Color::Black, Piece::Bishop),
-        );
+// every term of the evaluation by color, (middlegame, endgame), all from the point of view
+// of the color itself (a penalty is negative for both colors)
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct EvalTrace {
+    pub phase: i32,
+    pub terms: [[(i32, i32); 2]; 8],
+}
 
-        let mut black_bishop_score = 0;
+impl EvalTrace {
+    fn add(&mut self, term: EvalTerm, color: Color, (middlegame, endgame): (i32, i32)) {
+        let score = &mut self.terms[term as usize][color as usize];
 
-        for pos in black_bishop_position.iter() {
-            black_bishop_score += ENDGAME_BISHOP + BLACK_BISHOP_BONUS[pos.1 as usize];
-        }
+        score.0 += middlegame;
+        score.1 += endgame;
+    }
 
-        // black rooks
-        let black_rook_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Rook));
+    // white minus black, (middlegame, endgame)
+    pub fn term_score(&self, term: EvalTerm) -> (i32, i32) {
+        let [white, black] = self.terms[term as usize];
 
-        let mut black_rook_score = 0;
+        (white.0 - black.0, white.1 - black.1)
+    }
 
-        for pos in black_rook_position.iter() {
-            black_rook_score += ENDGAME_ROOK + BLACK_ROOK_BONUS[pos.1 as usize];
-        }
+    // tapered score from the point of view of white
+    pub fn score(&self) -> i32 {
+        let (middlegame_score, endgame_score) =
+            EVAL_TERMS
+                .iter()
+                .fold((0, 0), |(middlegame, endgame), term| {
+                    let score = self.term_score(*term);
 
-        // black queen
-        let black_queen_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Queen));
+                    (middlegame + score.0, endgame + score.1)
+                });
 
-        let mut black_queen_score = 0;
+        taper(middlegame_score, endgame_score, self.phase)
+    }
 
-        for pos in black_queen_position.iter() {
-            black_queen_score += ENDGAME_QUEEN + BLACK_QUEEN_BONUS[pos.1 as usize];
+    pub fn print(&self) {
+        println!(
+            "{:<14}|{:>8}{:>8} |{:>8}{:>8} |{:>8}{:>8}",
+            "term", "white mg", "eg", "black mg", "eg", "total mg", "eg"
+        );
+        println!("{}", "-".repeat(68));
+
+        for term in EVAL_TERMS.iter() {
+            let [white, black] = self.terms[*term as usize];
+            let total = self.term_score(*term);
+
+            println!(
+                "{:<14}|{:>8}{:>8} |{:>8}{:>8} |{:>8}{:>8}",
+                term.name(),
+                white.0,
+                white.1,
+                black.0,
+                black.1,
+                total.0,
+                total.1
+            );
         }
 
-        // black king
-        let black_king_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Queen));
+        println!("{}", "-".repeat(68));
+        println!("phase: {} / {}", self.phase, TOTAL_PHASE);
+        println!("score: {} (white side)", self.score());
+    }
+}
 
-        let mut black_king_bonus = 0;
+// evaluation with every term recorded, the score is from the point of view of white
+pub fn trace(board: &BoardState, pawn_table: &mut PawnHashTable, params: &EvalParams) -> EvalTrace {
+    let mut trace = EvalTrace {
+        phase: game_phase(board),
+        ..EvalTrace::default()
+    };
 
-        for pos in black_king_position.iter() {
-            black_king_bonus += BLACK_KING_ENDGAME_BONUS[pos.1 as usize];
-        }
+    // material and piece square totals come from the tables set on the board
+    debug_assert_eq!(
+        (board.material, board.piece_square),
+        board.psq_tables.totals(board)
+    );
 
-        // other bonuses
-
-        // bishop pair
-        let white_bishop_pair_bonus =
-            match board.get_piece_bb(Color::White, Piece::Bishop).count_ones() == 2 {
-                true => 50,
-                false => 0,
-            };
-
-        let black_bishop_pair_bonus =
-            match board.get_piece_bb(Color::Black, Piece::Bishop).count_ones() == 2 {
-                true => 50,
-                false => 0,
-            };
-        ((white_pawn_score
-            + white_knight_score
-            + white_bishop_score
-            + white_rook_score
-            + white_queen_score
-            + white_king_bonus
-            + white_bishop_pair_bonus)
-            - (black_pawn_score
-                + black_knight_score
-                + black_bishop_score
-                + black_rook_score
-                + black_queen_score
-                + black_king_bonus
-                + black_bishop_pair_bonus))
-            * color_const
-    } else {
-        // middle game / opening
-        // white pawns
-        let white_pawn_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Pawn));
-
-        let mut white_pawn_score = 0;
-
-        for pos in white_pawn_position.iter() {
-            white_pawn_score += PAWN + WHITE_PAWN_MIDDLEGAME_BONUS[pos.1 as usize];
-        }
+    let pawn_entry = pawn_table.get(board, params);
 
-        // white knights
-        let white_knight_position = parse_bitboards(
-            Color::White,
-            board.get_piece_bb(Color::White, Piece::Knight),
+    for color in [Color::White, Color::Black] {
+        trace.add(EvalTerm::Material, color, board.material[color as usize]);
+        trace.add(
+            EvalTerm::PieceSquare,
+            color,
+            board.piece_square[color as usize],
         );
 
-        let mut white_knight_score = 0;
-
-        for pos in white_knight_position.iter() {
-            white_knight_score += KNIGHT + WHITE_KNIGHT_BONUS[pos.1 as usize];
+        if board.get_piece_bb(color, Piece::Bishop).count_ones() == 2 {
+            trace.add(EvalTerm::BishopPair, color, params.bishop_pair);
         }
 
-        // white bishops
-        let white_bishop_position = parse_bitboards(
-            Color::White,
-            board.get_piece_bb(Color::White, Piece::Bishop),
+        trace.add(EvalTerm::Pawns, color, pawn_entry.scores[color as usize]);
+        trace.add(
+            EvalTerm::PassedPawns,
+            color,
+            passed_pawn_score(board, color, pawn_entry.bb_passed[color as usize], params),
         );
-
-        let mut white_bishop_score = 0;
-
-        for pos in white_bishop_position.iter() {
-            white_bishop_score += BISHOP + WHITE_BISHOP_BONUS[pos.1 as usize];
-        }
-
-        // white rooks
-        let white_rook_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Rook));
-
-        let mut white_rook_score = 0;
-
-        for pos in white_rook_position.iter() {
-            white_rook_score += ROOK + WHITE_ROOK_BONUS[pos.1 as usize];
-        }
-
-        // white queen
-        let white_queen_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Queen));
-
-        let mut white_queen_score = 0;
-
-        for pos in white_queen_position.iter() {
-            white_queen_score += QUEEN + WHITE_QUEEN_BONUS[pos.1 as usize];
-        }
-
-        // white king
-        let white_king_position =
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::Queen));
-
-        let mut white_king_bonus = 0;
-
-        for pos in white_king_position.iter() {
-            white_king_bonus += WHITE_KING_MIDDLEGAME_BONUS[pos.1 as usize];
-        }
-
-        let black_pawn_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Pawn));
-
-        let mut black_pawn_score = 0;
-
-        for pos in black_pawn_position.iter() {
-            black_pawn_score += PAWN + BLACK_PAWN_MIDDLEGAME_BONUS[pos.1 as usize];
-        }
-
-        // black knights
-        let black_knight_position = parse_bitboards(
-            Color::Black,
-            board.get_piece_bb(Color::Black, Piece::Knight),
+        trace.add(
+            EvalTerm::Mobility,
+            color,
+            mobility_score(board, color, params),
         );
-
-        let mut black_knight_score = 0;
-
-        for pos in black_knight_position.iter() {
-            black_knight_score += KNIGHT + BLACK_KNIGHT_BONUS[pos.1 as usize];
-        }
-
-        // black bishops
-        let black_bishop_position = parse_bitboards(
-            Color::Black,
-            board.get_piece_bb(Color::Black, Piece::Bishop),
+        // only counts in middlegame
+        trace.add(
+            EvalTerm::KingSafety,
+            color,
+            (king_safety_score(board, color, params), 0),
         );
+        trace.add(
+            EvalTerm::Pieces,
+            color,
+            piece_terms_score(board, color, params),
+        );
+    }
 
-        let mut black_bishop_score = 0;
-
-        for pos in black_bishop_position.iter() {
-            black_bishop_score += BISHOP + BLACK_BISHOP_BONUS[pos.1 as usize];
-        }
-
-        // black rooks
-        let black_rook_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Rook));
-
-        let mut black_rook_score = 0;
-
-        for pos in black_rook_position.iter() {
-            black_rook_score += ROOK + BLACK_ROOK_BONUS[pos.1 as usize];
-        }
-
-        // black queen
-        let black_queen_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Queen));
-
-        let mut black_queen_score = 0;
-
-        for pos in black_queen_position.iter() {
-            black_queen_score += QUEEN + BLACK_QUEEN_BONUS[pos.1 as usize];
-        }
+    trace
+}
 
-        // black king
-        let black_king_position =
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::Queen));
+pub fn evaluate(board: &BoardState, pawn_table: &mut PawnHashTable, params: &EvalParams) -> i32 {
+    if let Some(nnue) = &board.nnue {
+        return nnue.network.evaluate(&nnue.accumulator, board.to_move);
+    }
 
-        let mut black_king_bonus = 0;
+    // for negamax, relative eval
+    let color_const = match board.to_move {
+        Color::White => 1,
+        Color::Black => -1,
+    };
 
-        for pos in black_king_position.iter() {
-            black_king_bonus += BLACK_KING_MIDDLEGAME_BONUS[pos.1 as usize];
+    // known endings first, they either replace or scale the general terms
+    let score = match probe_endgame(board) {
+        Some((_, EndgameResult::Score(score))) => score,
+        Some((_, EndgameResult::Scale(factor))) => {
+            trace(board, pawn_table, params).score() * factor / SCALE_NORMAL
         }
+        None => trace(board, pawn_table, params).score(),
+    };
 
-        // other bonuses
-
-        // bishop pair
-        let white_bishop_pair_bonus =
-            match board.get_piece_bb(Color::White, Piece::Bishop).count_ones() == 2 {
-                true => 50,
-                false => 0,
-            };
-
-        let black_bishop_pair_bonus =
-            match board.get_piece_bb(Color::Black, Piece::Bishop).count_ones() == 2 {
-                true => 50,
-                false => 0,
-            };
-
-        // king safety (if covered with pawns) only in middlegame and opening
-        let white_king_moves = generate_king_moves(
-            parse_bitboards(Color::White, board.get_piece_bb(Color::White, Piece::King)),
-            board.bb_fullboard,
-        );
-
-        let white_king_safety_bonus = match white_king_moves.len() {
-            0 => 100,
-            _ => {
-                if (board.get_color_bb(Color::White) & white_king_moves[0].1) <= 2 {
-                    100
-                } else {
-                    -20
-                }
-            }
-        };
-
-        let black_king_moves = generate_king_moves(
-            parse_bitboards(Color::Black, board.get_piece_bb(Color::Black, Piece::King)),
-            board.bb_fullboard,
-        );
-
-        let black_king_safety_bonus = match black_king_moves.len() {
-            0 => 100,
-            _ => {
-                if (board.get_color_bb(Color::Black) & black_king_moves[0].1).count_ones() <= 2 {
-                    100
-                } else {
-                    -20
-                }
-            }
-        };
-
-        // println!("white_pawn_score: {}", white_pawn_score);
-        // println!("knight: {}", white_knight_score);
-        // println!("bishop: {}", white_bishop_score);
-        // println!("rook: {}", white_rook_score);
-        // println!("queen: {}", white_queen_score);
-        // println!("white k {}", white_king_bonus);
-        // println!("bish pair {}", white_bishop_pair_bonus);
-        // println!("king saf {}\n", white_king_safety_bonus);
-        //
-        // println!("black_pawn_score: {}", black_pawn_score);
-        // println!("knight: {}", black_knight_score);
-        // println!("bishop: {}", black_bishop_score);
-        // println!("rook: {}", black_rook_score);
-        // println!("queen: {}", black_queen_score);
-        // println!("bl king{}", black_king_bonus);
-        // println!("bih pair {}", black_bishop_pair_bonus);
-        // println!("bl saf {}\n\n\n\n", black_king_safety_bonus);
-
-        ((white_pawn_score
-            + white_knight_score
-            + white_bishop_score
-            + white_rook_score
-            + white_queen_score
-            + white_king_bonus
-            + white_bishop_pair_bonus
-            + white_king_safety_bonus)
-            - (black_pawn_score
-                + black_knight_score
-                + black_bishop_score
-                + black_rook_score
-                + black_queen_score
-                + black_king_bonus
-                + black_bishop_pair_bonus
-                + black_king_safety_bonus))
-            * color_const
-    }
+    score * color_const
 }
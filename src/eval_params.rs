@@ -0,0 +1,422 @@
+use crate::{
+    eval::{
+        BISHOP, BISHOP_PAIR_BONUS, ENDGAME_BISHOP, ENDGAME_KNIGHT, ENDGAME_PAWN, ENDGAME_QUEEN,
+        ENDGAME_ROOK, KNIGHT, PAWN, QUEEN, ROOK, WHITE_BISHOP_ENDGAME_BONUS,
+        WHITE_BISHOP_MIDDLEGAME_BONUS, WHITE_KING_ENDGAME_BONUS, WHITE_KING_MIDDLEGAME_BONUS,
+        WHITE_KNIGHT_ENDGAME_BONUS, WHITE_KNIGHT_MIDDLEGAME_BONUS, WHITE_PAWN_ENDGAME_BONUS,
+        WHITE_PAWN_MIDDLEGAME_BONUS, WHITE_QUEEN_ENDGAME_BONUS, WHITE_QUEEN_MIDDLEGAME_BONUS,
+        WHITE_ROOK_ENDGAME_BONUS, WHITE_ROOK_MIDDLEGAME_BONUS,
+    },
+    king_safety::{
+        ATTACK_WEIGHTS, MISSING_SHIELD_PENALTY, OPEN_FILE_PENALTY, PAWN_SHIELD_BONUS,
+        PAWN_STORM_PENALTY, SAFETY_TABLE, SEMI_OPEN_FILE_PENALTY,
+    },
+    mobility::{
+        BISHOP_MOBILITY_ENDGAME, BISHOP_MOBILITY_MIDDLEGAME, KNIGHT_MOBILITY_ENDGAME,
+        KNIGHT_MOBILITY_MIDDLEGAME, QUEEN_MOBILITY_ENDGAME, QUEEN_MOBILITY_MIDDLEGAME,
+        ROOK_MOBILITY_ENDGAME, ROOK_MOBILITY_MIDDLEGAME,
+    },
+    pawn_structure::{
+        BACKWARD_PAWN_PENALTY, CONNECTED_PAWN_BONUS, DOUBLED_PAWN_PENALTY, ISOLATED_PAWN_PENALTY,
+        PASSED_PAWN_ENDGAME_BONUS, PASSED_PAWN_MIDDLEGAME_BONUS, PAWN_CHAIN_BONUS,
+    },
+    piece_terms::{
+        BAD_BISHOP_PENALTY, BISHOP_OUTPOST_BONUS, CONNECTED_ROOKS_BONUS, KNIGHT_OUTPOST_BONUS,
+        ROOK_OPEN_FILE_BONUS, ROOK_SEMI_OPEN_FILE_BONUS, ROOK_SEVENTH_RANK_BONUS,
+        TRAPPED_BISHOP_PENALTY, TRAPPED_ROOK_PENALTY,
+    },
+};
+use std::fs;
+
+// every evaluation weight, the consts next to each term are the defaults.
+// (i32, i32) values are (middlegame, endgame). the endgame scores and scale factors and
+// MIN_KING_ATTACKERS are deliberately left out, they aren't weights the tuner can fit
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    // pawn to queen, kings are always on the board so they don't count as material
+    pub middlegame_values: [i32; 5],
+    pub endgame_values: [i32; 5],
+    // bonus tables by piece from the point of view of white, black looks them up mirrored
+    pub middlegame_tables: [[i32; 64]; 6],
+    pub endgame_tables: [[i32; 64]; 6],
+    pub bishop_pair: (i32, i32),
+
+    pub doubled_pawn: (i32, i32),
+    pub isolated_pawn: (i32, i32),
+    pub backward_pawn: (i32, i32),
+    pub connected_pawn: (i32, i32),
+    pub pawn_chain: (i32, i32),
+    pub passed_pawn_middlegame: [i32; 8],
+    pub passed_pawn_endgame: [i32; 8],
+
+    pub knight_mobility_middlegame: [i32; 9],
+    pub knight_mobility_endgame: [i32; 9],
+    pub bishop_mobility_middlegame: [i32; 14],
+    pub bishop_mobility_endgame: [i32; 14],
+    pub rook_mobility_middlegame: [i32; 15],
+    pub rook_mobility_endgame: [i32; 15],
+    pub queen_mobility_middlegame: [i32; 28],
+    pub queen_mobility_endgame: [i32; 28],
+
+    pub pawn_shield: [i32; 8],
+    pub missing_shield: i32,
+    pub pawn_storm: [i32; 8],
+    pub king_semi_open_file: i32,
+    pub king_open_file: i32,
+    pub attack_weights: [i32; 6],
+    pub safety_table: [i32; 100],
+
+    pub rook_open_file: (i32, i32),
+    pub rook_semi_open_file: (i32, i32),
+    pub rook_seventh_rank: (i32, i32),
+    pub connected_rooks: (i32, i32),
+    pub bad_bishop: (i32, i32),
+    pub knight_outpost: (i32, i32),
+    pub bishop_outpost: (i32, i32),
+    pub trapped_rook: (i32, i32),
+    pub trapped_bishop: (i32, i32),
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            middlegame_values: [PAWN, KNIGHT, BISHOP, ROOK, QUEEN],
+            endgame_values: [
+                ENDGAME_PAWN,
+                ENDGAME_KNIGHT,
+                ENDGAME_BISHOP,
+                ENDGAME_ROOK,
+                ENDGAME_QUEEN,
+            ],
+            middlegame_tables: [
+                WHITE_PAWN_MIDDLEGAME_BONUS,
+                WHITE_KNIGHT_MIDDLEGAME_BONUS,
+                WHITE_BISHOP_MIDDLEGAME_BONUS,
+                WHITE_ROOK_MIDDLEGAME_BONUS,
+                WHITE_QUEEN_MIDDLEGAME_BONUS,
+                WHITE_KING_MIDDLEGAME_BONUS,
+            ],
+            endgame_tables: [
+                WHITE_PAWN_ENDGAME_BONUS,
+                WHITE_KNIGHT_ENDGAME_BONUS,
+                WHITE_BISHOP_ENDGAME_BONUS,
+                WHITE_ROOK_ENDGAME_BONUS,
+                WHITE_QUEEN_ENDGAME_BONUS,
+                WHITE_KING_ENDGAME_BONUS,
+            ],
+            bishop_pair: BISHOP_PAIR_BONUS,
+
+            doubled_pawn: DOUBLED_PAWN_PENALTY,
+            isolated_pawn: ISOLATED_PAWN_PENALTY,
+            backward_pawn: BACKWARD_PAWN_PENALTY,
+            connected_pawn: CONNECTED_PAWN_BONUS,
+            pawn_chain: PAWN_CHAIN_BONUS,
+            passed_pawn_middlegame: PASSED_PAWN_MIDDLEGAME_BONUS,
+            passed_pawn_endgame: PASSED_PAWN_ENDGAME_BONUS,
+
+            knight_mobility_middlegame: KNIGHT_MOBILITY_MIDDLEGAME,
+            knight_mobility_endgame: KNIGHT_MOBILITY_ENDGAME,
+            bishop_mobility_middlegame: BISHOP_MOBILITY_MIDDLEGAME,
+            bishop_mobility_endgame: BISHOP_MOBILITY_ENDGAME,
+            rook_mobility_middlegame: ROOK_MOBILITY_MIDDLEGAME,
+            rook_mobility_endgame: ROOK_MOBILITY_ENDGAME,
+            queen_mobility_middlegame: QUEEN_MOBILITY_MIDDLEGAME,
+            queen_mobility_endgame: QUEEN_MOBILITY_ENDGAME,
+
+            pawn_shield: PAWN_SHIELD_BONUS,
+            missing_shield: MISSING_SHIELD_PENALTY,
+            pawn_storm: PAWN_STORM_PENALTY,
+            king_semi_open_file: SEMI_OPEN_FILE_PENALTY,
+            king_open_file: OPEN_FILE_PENALTY,
+            attack_weights: ATTACK_WEIGHTS,
+            safety_table: SAFETY_TABLE,
+
+            rook_open_file: ROOK_OPEN_FILE_BONUS,
+            rook_semi_open_file: ROOK_SEMI_OPEN_FILE_BONUS,
+            rook_seventh_rank: ROOK_SEVENTH_RANK_BONUS,
+            connected_rooks: CONNECTED_ROOKS_BONUS,
+            bad_bishop: BAD_BISHOP_PENALTY,
+            knight_outpost: KNIGHT_OUTPOST_BONUS,
+            bishop_outpost: BISHOP_OUTPOST_BONUS,
+            trapped_rook: TRAPPED_ROOK_PENALTY,
+            trapped_bishop: TRAPPED_BISHOP_PENALTY,
+        }
+    }
+}
+
+impl EvalParams {
+    // every parameter as (const name, rust type, values), in a fixed order. names and types
+    // are the ones of the consts the defaults come from, so the tuner output replaces them
+    pub fn fields_mut(&mut self) -> Vec<(&'static str, &'static str, Vec<&mut i32>)> {
+        fn values<const N: usize>(array: &mut [i32; N]) -> Vec<&mut i32> {
+            array.iter_mut().collect()
+        }
+
+        fn pair(pair: &mut (i32, i32)) -> Vec<&mut i32> {
+            vec![&mut pair.0, &mut pair.1]
+        }
+
+        let mut fields = vec![];
+
+        for (name, value) in ["PAWN", "KNIGHT", "BISHOP", "ROOK", "QUEEN"]
+            .into_iter()
+            .zip(self.middlegame_values.iter_mut())
+        {
+            fields.push((name, "i32", vec![value]));
+        }
+
+        for (name, value) in [
+            "ENDGAME_PAWN",
+            "ENDGAME_KNIGHT",
+            "ENDGAME_BISHOP",
+            "ENDGAME_ROOK",
+            "ENDGAME_QUEEN",
+        ]
+        .into_iter()
+        .zip(self.endgame_values.iter_mut())
+        {
+            fields.push((name, "i32", vec![value]));
+        }
+
+        for (name, table) in [
+            "WHITE_PAWN_MIDDLEGAME_BONUS",
+            "WHITE_KNIGHT_MIDDLEGAME_BONUS",
+            "WHITE_BISHOP_MIDDLEGAME_BONUS",
+            "WHITE_ROOK_MIDDLEGAME_BONUS",
+            "WHITE_QUEEN_MIDDLEGAME_BONUS",
+            "WHITE_KING_MIDDLEGAME_BONUS",
+        ]
+        .into_iter()
+        .zip(self.middlegame_tables.iter_mut())
+        {
+            fields.push((name, "[i32; 64]", values(table)));
+        }
+
+        for (name, table) in [
+            "WHITE_PAWN_ENDGAME_BONUS",
+            "WHITE_KNIGHT_ENDGAME_BONUS",
+            "WHITE_BISHOP_ENDGAME_BONUS",
+            "WHITE_ROOK_ENDGAME_BONUS",
+            "WHITE_QUEEN_ENDGAME_BONUS",
+            "WHITE_KING_ENDGAME_BONUS",
+        ]
+        .into_iter()
+        .zip(self.endgame_tables.iter_mut())
+        {
+            fields.push((name, "[i32; 64]", values(table)));
+        }
+
+        fields.extend(vec![
+            (
+                "BISHOP_PAIR_BONUS",
+                "(i32, i32)",
+                pair(&mut self.bishop_pair),
+            ),
+            (
+                "DOUBLED_PAWN_PENALTY",
+                "(i32, i32)",
+                pair(&mut self.doubled_pawn),
+            ),
+            (
+                "ISOLATED_PAWN_PENALTY",
+                "(i32, i32)",
+                pair(&mut self.isolated_pawn),
+            ),
+            (
+                "BACKWARD_PAWN_PENALTY",
+                "(i32, i32)",
+                pair(&mut self.backward_pawn),
+            ),
+            (
+                "CONNECTED_PAWN_BONUS",
+                "(i32, i32)",
+                pair(&mut self.connected_pawn),
+            ),
+            ("PAWN_CHAIN_BONUS", "(i32, i32)", pair(&mut self.pawn_chain)),
+            (
+                "PASSED_PAWN_MIDDLEGAME_BONUS",
+                "[i32; 8]",
+                values(&mut self.passed_pawn_middlegame),
+            ),
+            (
+                "PASSED_PAWN_ENDGAME_BONUS",
+                "[i32; 8]",
+                values(&mut self.passed_pawn_endgame),
+            ),
+            (
+                "KNIGHT_MOBILITY_MIDDLEGAME",
+                "[i32; 9]",
+                values(&mut self.knight_mobility_middlegame),
+            ),
+            (
+                "KNIGHT_MOBILITY_ENDGAME",
+                "[i32; 9]",
+                values(&mut self.knight_mobility_endgame),
+            ),
+            (
+                "BISHOP_MOBILITY_MIDDLEGAME",
+                "[i32; 14]",
+                values(&mut self.bishop_mobility_middlegame),
+            ),
+            (
+                "BISHOP_MOBILITY_ENDGAME",
+                "[i32; 14]",
+                values(&mut self.bishop_mobility_endgame),
+            ),
+            (
+                "ROOK_MOBILITY_MIDDLEGAME",
+                "[i32; 15]",
+                values(&mut self.rook_mobility_middlegame),
+            ),
+            (
+                "ROOK_MOBILITY_ENDGAME",
+                "[i32; 15]",
+                values(&mut self.rook_mobility_endgame),
+            ),
+            (
+                "QUEEN_MOBILITY_MIDDLEGAME",
+                "[i32; 28]",
+                values(&mut self.queen_mobility_middlegame),
+            ),
+            (
+                "QUEEN_MOBILITY_ENDGAME",
+                "[i32; 28]",
+                values(&mut self.queen_mobility_endgame),
+            ),
+            (
+                "PAWN_SHIELD_BONUS",
+                "[i32; 8]",
+                values(&mut self.pawn_shield),
+            ),
+            (
+                "MISSING_SHIELD_PENALTY",
+                "i32",
+                vec![&mut self.missing_shield],
+            ),
+            (
+                "PAWN_STORM_PENALTY",
+                "[i32; 8]",
+                values(&mut self.pawn_storm),
+            ),
+            (
+                "SEMI_OPEN_FILE_PENALTY",
+                "i32",
+                vec![&mut self.king_semi_open_file],
+            ),
+            ("OPEN_FILE_PENALTY", "i32", vec![&mut self.king_open_file]),
+            (
+                "ATTACK_WEIGHTS",
+                "[i32; 6]",
+                values(&mut self.attack_weights),
+            ),
+            ("SAFETY_TABLE", "[i32; 100]", values(&mut self.safety_table)),
+            (
+                "ROOK_OPEN_FILE_BONUS",
+                "(i32, i32)",
+                pair(&mut self.rook_open_file),
+            ),
+            (
+                "ROOK_SEMI_OPEN_FILE_BONUS",
+                "(i32, i32)",
+                pair(&mut self.rook_semi_open_file),
+            ),
+            (
+                "ROOK_SEVENTH_RANK_BONUS",
+                "(i32, i32)",
+                pair(&mut self.rook_seventh_rank),
+            ),
+            (
+                "CONNECTED_ROOKS_BONUS",
+                "(i32, i32)",
+                pair(&mut self.connected_rooks),
+            ),
+            (
+                "BAD_BISHOP_PENALTY",
+                "(i32, i32)",
+                pair(&mut self.bad_bishop),
+            ),
+            (
+                "KNIGHT_OUTPOST_BONUS",
+                "(i32, i32)",
+                pair(&mut self.knight_outpost),
+            ),
+            (
+                "BISHOP_OUTPOST_BONUS",
+                "(i32, i32)",
+                pair(&mut self.bishop_outpost),
+            ),
+            (
+                "TRAPPED_ROOK_PENALTY",
+                "(i32, i32)",
+                pair(&mut self.trapped_rook),
+            ),
+            (
+                "TRAPPED_BISHOP_PENALTY",
+                "(i32, i32)",
+                pair(&mut self.trapped_bishop),
+            ),
+        ]);
+
+        fields
+    }
+}
+
+impl EvalParams {
+    // plain text, one "NAME value value ..." line per param in the order of fields_mut,
+    // lines starting with # are comments
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# ravissant eval params\n");
+
+        for (name, _, values) in self.clone().fields_mut() {
+            let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+
+            text += &format!("{} {}\n", name, values.join(" "));
+        }
+
+        text
+    }
+
+    // params missing from the text keep their default values
+    pub fn from_text(text: &str) -> Result<EvalParams, &'static str> {
+        let mut params = EvalParams::default();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let name = tokens.next().unwrap_or_default();
+            let values = tokens
+                .map(|token| token.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| "Incorrect value in the eval params!")?;
+
+            let mut fields = params.fields_mut();
+            let (_, _, field) = fields
+                .iter_mut()
+                .find(|(field_name, _, _)| *field_name == name)
+                .ok_or("Unknown param in the eval params!")?;
+
+            if field.len() != values.len() {
+                return Err("Incorrect values count in the eval params!");
+            }
+
+            for (param, value) in field.iter_mut().zip(values) {
+                **param = value;
+            }
+        }
+
+        Ok(params)
+    }
+
+    pub fn load(path: &str) -> Result<EvalParams, &'static str> {
+        let text = fs::read_to_string(path).map_err(|_| "Can't read the eval params file!")?;
+
+        EvalParams::from_text(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), &'static str> {
+        fs::write(path, self.to_text()).map_err(|_| "Can't write the eval params file!")
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    board::{Bitboard, BoardState, Color, Piece},
+    constants::{BOARD_SQUARES, KING_ATTACKS},
+    eval_params::EvalParams,
+    mobility::piece_attacks,
+    pawn_structure::{rows_ahead, FILES},
+    piece_parsing::parse_bitboards,
+    utils::opposite_color,
+};
+
+// by distance in rows from the king to the closest friendly pawn in front of it on the file
+pub const PAWN_SHIELD_BONUS: [i32; 8] = [0, 20, 10, 5, 0, 0, 0, 0];
+pub const MISSING_SHIELD_PENALTY: i32 = -25;
+// by distance in rows from the king to the closest enemy pawn in front of it on the file,
+// a pawn right in front of the king is usually stuck against it
+pub const PAWN_STORM_PENALTY: [i32; 8] = [0, -5, -30, -20, -10, -5, 0, 0];
+
+// files next to the king without friendly pawns, and without any pawns
+pub const SEMI_OPEN_FILE_PENALTY: i32 = -15;
+pub const OPEN_FILE_PENALTY: i32 = -10;
+
+// attack units for every attacked square of the king zone, indexed by piece
+pub const ATTACK_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+
+// attackers needed before the king zone attacks count. it's a count and not a weight, so
+// it's deliberately fixed and not in EvalParams, the tuner shapes SAFETY_TABLE instead
+pub const MIN_KING_ATTACKERS: u32 = 2;
+
+// attack units to penalty, grows slowly for a few units and then fast,
+// one lone attacker is not much of a threat but several together are
+pub const SAFETY_TABLE: [i32; 100] = [
+    0, 0, 1, 2, 3, 5, 7, 9, 12, 15, 18, 22, 26, 30, 35, 39, 44, 50, 56, 62, 68, 75, 82, 85, 89, 97,
+    105, 113, 122, 131, 140, 150, 169, 180, 191, 202, 213, 225, 237, 248, 260, 272, 283, 295, 307,
+    319, 330, 342, 354, 366, 377, 389, 401, 412, 424, 436, 448, 459, 471, 483, 494, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+// closest pawn to the row in front of it, none if the bitboard is empty
+fn closest_pawn(color: Color, bb_pawns: Bitboard) -> Option<usize> {
+    if bb_pawns == 0 {
+        return None;
+    }
+
+    match color {
+        Color::White => Some(63 - bb_pawns.leading_zeros() as usize),
+        Color::Black => Some(bb_pawns.trailing_zeros() as usize),
+    }
+}
+
+// pawn shield, pawn storm and open files on the king file and the files next to it
+fn king_shelter_score(
+    board: &BoardState,
+    color: Color,
+    king_square: usize,
+    params: &EvalParams,
+) -> i32 {
+    let bb_pawns = board.get_piece_bb(color, Piece::Pawn);
+    let bb_enemy_pawns = board.get_piece_bb(opposite_color(color), Piece::Pawn);
+    let king_row = king_square / 8;
+    let king_file = king_square % 8;
+    let bb_ahead = rows_ahead(color, king_row);
+
+    let mut score = 0;
+
+    for bb_file in FILES[king_file.saturating_sub(1)..=(king_file + 1).min(7)].iter() {
+        match closest_pawn(color, bb_pawns & bb_file & bb_ahead) {
+            Some(square) => score += params.pawn_shield[king_row.abs_diff(square / 8)],
+            None => score += params.missing_shield,
+        }
+
+        if let Some(square) = closest_pawn(color, bb_enemy_pawns & bb_file & bb_ahead) {
+            score += params.pawn_storm[king_row.abs_diff(square / 8)];
+        }
+
+        if bb_pawns & bb_file == 0 {
+            score += params.king_semi_open_file;
+
+            if bb_enemy_pawns & bb_file == 0 {
+                score += params.king_open_file;
+            }
+        }
+    }
+
+    score
+}
+
+// enemy pieces attacking the squares around the king, one attacker alone doesn't count
+fn king_attack_score(
+    board: &BoardState,
+    color: Color,
+    king_square: usize,
+    params: &EvalParams,
+) -> i32 {
+    let enemy_color = opposite_color(color);
+    let bb_king_zone = KING_ATTACKS[king_square] | BOARD_SQUARES[king_square];
+
+    let mut attackers = 0;
+    let mut attack_units = 0;
+
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for square in parse_bitboards(enemy_color, board.get_piece_bb(enemy_color, piece)).iter() {
+            let bb_attacked =
+                piece_attacks(piece, square.1 as usize, board.bb_fullboard) & bb_king_zone;
+
+            if bb_attacked != 0 {
+                attackers += 1;
+                attack_units +=
+                    params.attack_weights[piece as usize] * bb_attacked.count_ones() as i32;
+            }
+        }
+    }
+
+    if attackers < MIN_KING_ATTACKERS {
+        return 0;
+    }
+
+    -params.safety_table[(attack_units.max(0) as usize).min(params.safety_table.len() - 1)]
+}
+
+// king safety of one color, only counts in middlegame so it fades out with the phase
+// instead of having an endgame weight
+pub fn king_safety_score(board: &BoardState, color: Color, params: &EvalParams) -> i32 {
+    let bb_king = board.get_piece_bb(color, Piece::King);
+
+    if bb_king == 0 {
+        return 0;
+    }
+
+    let king_square = bb_king.trailing_zeros() as usize;
+
+    king_shelter_score(board, color, king_square, params)
+        + king_attack_score(board, color, king_square, params)
+}
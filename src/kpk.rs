@@ -0,0 +1,210 @@
+use crate::{
+    board::{Bitboard, Color},
+    constants::{BOARD_SQUARES, KING_ATTACKS, PAWN_ATTACKS},
+};
+use std::sync::OnceLock;
+
+// king and pawn against king with white as the side with the pawn. every position is
+// classified by retrograde analysis: the obvious wins and draws first, then positions
+// are resolved from the positions they lead to until nothing changes. what's left can't
+// be won by white
+//
+// index is side to move | black king << 1 | white king << 7 | pawn << 13
+pub const KPK_SIZE: usize = 2 * 64 * 64 * 64;
+
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+static KPK_BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+fn kpk_index(white_king: usize, pawn: usize, black_king: usize, to_move: Color) -> usize {
+    to_move as usize | black_king << 1 | white_king << 7 | pawn << 13
+}
+
+// builds the bitbase if it isn't built yet, the engine does it at startup
+pub fn init_kpk() {
+    KPK_BITBASE.get_or_init(generate_kpk);
+}
+
+// true when white wins with its king on white_king and pawn on pawn against the black
+// king on black_king, squares from the point of view of white
+pub fn kpk_probe(white_king: usize, pawn: usize, black_king: usize, to_move: Color) -> bool {
+    let index = kpk_index(white_king, pawn, black_king, to_move);
+
+    KPK_BITBASE.get_or_init(generate_kpk)[index / 64] & 1 << (index % 64) != 0
+}
+
+fn initial_result(white_king: usize, pawn: usize, black_king: usize, to_move: Color) -> u8 {
+    let bb_pawn_attacks = PAWN_ATTACKS[Color::White as usize][pawn];
+    let row = pawn / 8;
+
+    // pawns can't stand on the first or the last rank
+    if row == 0
+        || row == 7
+        || white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || KING_ATTACKS[white_king] & BOARD_SQUARES[black_king] != 0
+    {
+        return INVALID;
+    }
+
+    match to_move {
+        Color::White => {
+            // black can't be in check with white to move
+            if bb_pawn_attacks & BOARD_SQUARES[black_king] != 0 {
+                return INVALID;
+            }
+
+            // pawn on the seventh queens safely
+            let queening_square = pawn - 8;
+
+            if row == 1
+                && white_king != queening_square
+                && black_king != queening_square
+                && (KING_ATTACKS[black_king] & BOARD_SQUARES[queening_square] == 0
+                    || KING_ATTACKS[white_king] & BOARD_SQUARES[queening_square] != 0)
+            {
+                return WIN;
+            }
+        }
+        Color::Black => {
+            let bb_moves = KING_ATTACKS[black_king] & !(KING_ATTACKS[white_king] | bb_pawn_attacks);
+
+            if bb_moves == 0 {
+                return match bb_pawn_attacks & BOARD_SQUARES[black_king] != 0 {
+                    true => WIN,
+                    false => DRAW,
+                };
+            }
+
+            // undefended pawn gets taken
+            if bb_moves & BOARD_SQUARES[pawn] != 0 {
+                return DRAW;
+            }
+        }
+    }
+
+    UNKNOWN
+}
+
+fn squares(mut bb: Bitboard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        let square = (bb != 0).then(|| bb.trailing_zeros() as usize);
+        bb &= bb.wrapping_sub(1);
+        square
+    })
+}
+
+// result of a position from the results of the positions it leads to
+fn resolve(
+    results: &[u8],
+    white_king: usize,
+    pawn: usize,
+    black_king: usize,
+    to_move: Color,
+) -> u8 {
+    let mut children = 0;
+
+    match to_move {
+        Color::White => {
+            let bb_moves =
+                KING_ATTACKS[white_king] & !KING_ATTACKS[black_king] & !BOARD_SQUARES[pawn];
+
+            for square in squares(bb_moves) {
+                children |= results[kpk_index(square, pawn, black_king, Color::Black)];
+            }
+
+            // pushes from the seventh are promotions, handled in initial_result
+            let push = pawn - 8;
+
+            if pawn / 8 > 1 && push != white_king && push != black_king {
+                children |= results[kpk_index(white_king, push, black_king, Color::Black)];
+
+                let double_push = pawn - 16;
+
+                if pawn / 8 == 6 && double_push != white_king && double_push != black_king {
+                    children |=
+                        results[kpk_index(white_king, double_push, black_king, Color::Black)];
+                }
+            }
+
+            // one winning move is enough, a draw needs every move to draw
+            match children {
+                _ if children & WIN != 0 => WIN,
+                _ if children & UNKNOWN != 0 => UNKNOWN,
+                _ => DRAW,
+            }
+        }
+        Color::Black => {
+            let bb_moves = KING_ATTACKS[black_king]
+                & !(KING_ATTACKS[white_king] | PAWN_ATTACKS[Color::White as usize][pawn]);
+
+            for square in squares(bb_moves) {
+                children |= results[kpk_index(white_king, pawn, square, Color::White)];
+            }
+
+            match children {
+                _ if children & DRAW != 0 => DRAW,
+                _ if children & UNKNOWN != 0 => UNKNOWN,
+                _ => WIN,
+            }
+        }
+    }
+}
+
+pub fn generate_kpk() -> Vec<u64> {
+    let mut results = vec![INVALID; KPK_SIZE];
+
+    for (index, result) in results.iter_mut().enumerate() {
+        let (to_move, black_king, white_king, pawn) =
+            (index & 1, index >> 1 & 63, index >> 7 & 63, index >> 13);
+        let to_move = match to_move {
+            0 => Color::White,
+            _ => Color::Black,
+        };
+
+        *result = initial_result(white_king, pawn, black_king, to_move);
+    }
+
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for index in 0..KPK_SIZE {
+            if results[index] != UNKNOWN {
+                continue;
+            }
+
+            let to_move = match index & 1 {
+                0 => Color::White,
+                _ => Color::Black,
+            };
+            let result = resolve(
+                &results,
+                index >> 7 & 63,
+                index >> 13,
+                index >> 1 & 63,
+                to_move,
+            );
+
+            if result != UNKNOWN {
+                results[index] = result;
+                changed = true;
+            }
+        }
+    }
+
+    let mut bitbase = vec![0u64; KPK_SIZE / 64];
+
+    for (index, result) in results.iter().enumerate() {
+        if *result == WIN {
+            bitbase[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    bitbase
+}
@@ -1,28 +1,468 @@
-use std::process::exit;
+use std::{
+    env, fs,
+    io::stdin,
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
 
+mod bench;
 mod board;
+mod book_builder;
 mod constants;
+mod endgame;
+mod epd;
 mod eval;
+mod eval_params;
+mod king_safety;
+mod kpk;
 mod magic;
+mod mobility;
 mod move_generation;
+mod nnue;
+mod options;
+mod pawn_hash;
+mod pawn_structure;
+mod pgn;
 mod piece_parsing;
+mod piece_terms;
+mod polyglot;
+mod san;
 mod search;
+mod see;
+mod selfplay;
+mod smp;
+mod tt;
+mod tune;
 mod utils;
+mod zobrist;
 
+use bench::bench;
 use board::BoardState;
+use book_builder::{make_book, BookFilter, DEFAULT_BOOK_OUTPUT};
 use constants::DEFAULT_FEN_STRING;
+use endgame::{probe_endgame, EndgameResult, SCALE_NORMAL};
+use epd::{run_epd, DEFAULT_EPD_MOVETIME};
+use eval::{evaluate, trace, PieceSquareTables};
+use kpk::init_kpk;
+use options::EngineOptions;
+use pawn_hash::{PawnHashTable, DEFAULT_PAWN_HASH_SIZE_MB};
+use pgn::{load_pgn, write_pgn, PgnHeader};
+use polyglot::{book_move_to_string, polyglot_key, Book};
+use search::SearchLimits;
+use selfplay::{self_play, DEFAULT_SELFPLAY_PLIES};
+use smp::lazy_smp;
+use tt::TranspositionTable;
+use tune::{tune, DEFAULT_TUNE_ITERATIONS, DEFAULT_TUNE_OUTPUT};
+use utils::move_to_string;
 
-use search::negamax;
+const DEFAULT_DEPTH: u8 = 3;
 
+// usage:
+//   ravissant [--fen <fen>] [--depth <n>] [--nodes <n>] [--movetime <ms>] [--mate <n>]
+//             [--infinite] [--threads <n>] [--hash <mb>] [--multipv <n>]
+//             [--book <file> --ownbook true [--bookmode best|random]]
+//   ravissant bench [--depth <n>] [--threads <n>] [--hash <mb>]
+//   ravissant eval [--fen <fen>]
+//   ravissant tune <file> [--iterations <n>] [--output <file>]
+//   ravissant params <file>
+//   ravissant epd <file> [--movetime <ms>] [search limits]
+//   ravissant book <file> [--fen <fen>]
+//   ravissant pgn <file>
+//   ravissant selfplay [--fen <fen>] [search limits] [--plies <n>] [--output <file>]
+//   ravissant makebook <pgn file> [--output <file>] [--plies <n>] [--mincount <n>]
+//             [--result 1-0|0-1|1/2-1/2]
+//
+// every command takes --evalfile <file> to use eval params saved by `ravissant params`,
+// and --nnuefile <file> --usennue true to evaluate with a network instead
+//
+// with --infinite the search runs until "stop" is read from stdin
 fn main() {
-    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap_or_else(|err| {
-        println!("{}", err);
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (command, flags) = match args.first() {
+        Some(arg) if !arg.starts_with("--") => (arg.as_str(), &args[1..]),
+        _ => ("search", &args[..]),
+    };
+
+    // commands working on a file take its path right after the command
+    let (path, flags) = match flags.first() {
+        Some(arg) if !arg.starts_with("--") => (Some(arg.as_str()), &flags[1..]),
+        _ => (None, flags),
+    };
+
+    let mut options = EngineOptions::default();
+    let mut fen = DEFAULT_FEN_STRING.to_string();
+    let mut limits = SearchLimits::default();
+    let mut iterations = DEFAULT_TUNE_ITERATIONS;
+    let mut output = None;
+    let mut plies = None;
+    let mut book_filter = BookFilter::default();
+
+    let mut index = 0;
+
+    while index < flags.len() {
+        let name = flags[index].strip_prefix("--").unwrap_or_else(|| {
+            println!("Incorrect arguments!");
+            exit(1);
+        });
+
+        if name == "infinite" {
+            limits.infinite = true;
+            index += 1;
+            continue;
+        }
+
+        let value = flags.get(index + 1).unwrap_or_else(|| {
+            println!("Missing value for --{}!", name);
+            exit(1);
+        });
+
+        index += 2;
+
+        let result = match name {
+            "fen" => {
+                fen = value.to_string();
+                Ok(())
+            }
+            "depth" => match value.parse::<u8>() {
+                Ok(depth) if depth > 0 => {
+                    limits.depth = Some(depth);
+                    Ok(())
+                }
+                _ => Err("Incorrect depth!"),
+            },
+            "nodes" => match value.parse::<u64>() {
+                Ok(nodes) => {
+                    limits.nodes = Some(nodes);
+                    Ok(())
+                }
+                _ => Err("Incorrect nodes limit!"),
+            },
+            "movetime" => match value.parse::<u64>() {
+                Ok(movetime) => {
+                    limits.movetime = Some(movetime);
+                    Ok(())
+                }
+                _ => Err("Incorrect movetime!"),
+            },
+            "mate" => match value.parse::<u8>() {
+                Ok(mate) if mate > 0 => {
+                    limits.mate = Some(mate);
+                    Ok(())
+                }
+                _ => Err("Incorrect mate limit!"),
+            },
+            "iterations" => match value.parse::<usize>() {
+                Ok(count) if count > 0 => {
+                    iterations = count;
+                    Ok(())
+                }
+                _ => Err("Incorrect iterations count!"),
+            },
+            "output" => {
+                output = Some(value.to_string());
+                Ok(())
+            }
+            "plies" => match value.parse::<usize>() {
+                Ok(count) if count > 0 => {
+                    plies = Some(count);
+                    Ok(())
+                }
+                _ => Err("Incorrect plies count!"),
+            },
+            "mincount" => match value.parse::<u32>() {
+                Ok(count) => {
+                    book_filter.min_count = count;
+                    Ok(())
+                }
+                _ => Err("Incorrect minimum count!"),
+            },
+            "result" => match value.as_str() {
+                "1-0" | "0-1" | "1/2-1/2" => {
+                    book_filter.result = Some(value.to_string());
+                    Ok(())
+                }
+                _ => Err("Incorrect game result!"),
+            },
+            _ => options.set_option(name, value),
+        };
+
+        if let Err(err) = result {
+            println!("{}", err);
+            exit(1);
+        }
+    }
+
+    if let Some(plies) = plies {
+        book_filter.max_plies = plies;
+    }
+
+    if options.use_nnue && options.network.is_none() {
+        println!("UseNNUE needs a network from NNUEFile!");
         exit(1);
-    });
+    }
+
+    // without any limit a fixed depth search is done, unless it's infinite. test suites
+    // are timed like they are meant to be run
+    if !limits.infinite
+        && limits.depth.is_none()
+        && limits.nodes.is_none()
+        && limits.movetime.is_none()
+        && limits.mate.is_none()
+    {
+        match command {
+            "epd" => limits.movetime = Some(DEFAULT_EPD_MOVETIME),
+            _ => limits.depth = Some(DEFAULT_DEPTH),
+        }
+    }
+
+    // the kpk bitbase is built once up front instead of in the middle of a search
+    init_kpk();
+
+    match command {
+        "search" => {
+            let board = BoardState::from_fen(&fen).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            if let Some(book_move) = options.book_move(&board) {
+                println!("bestmove {} book", move_to_string(book_move));
+                return;
+            }
+
+            let tt = Arc::new(TranspositionTable::new(options.hash_mb));
+            let stop = Arc::new(AtomicBool::new(false));
+
+            if limits.infinite {
+                let stdin_stop = Arc::clone(&stop);
+
+                thread::spawn(move || {
+                    for line in stdin().lines().map_while(Result::ok) {
+                        if line.trim() == "stop" {
+                            stdin_stop.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                });
+            }
+
+            let result = lazy_smp(&board, limits, &options, tt, stop);
+
+            if options.multipv > 1 {
+                for (index, line) in result.pv_lines.iter().enumerate() {
+                    println!(
+                        "multipv {} score {} pv {}",
+                        index + 1,
+                        line.score,
+                        line.moves
+                            .iter()
+                            .map(|piece_move| move_to_string(*piece_move))
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                    );
+                }
+            }
+
+            println!(
+                "bestmove {} score {} depth {} nodes {}",
+                result
+                    .best_move
+                    .map(move_to_string)
+                    .unwrap_or("none".to_string()),
+                result.score,
+                result.depth,
+                result.nodes
+            );
+        }
+        "eval" => {
+            let mut board = BoardState::from_fen(&fen).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            board.set_psq_tables(Arc::new(PieceSquareTables::new(&options.eval_params)));
+
+            trace(
+                &board,
+                &mut PawnHashTable::new(DEFAULT_PAWN_HASH_SIZE_MB),
+                &options.eval_params,
+            )
+            .print();
+
+            match probe_endgame(&board) {
+                Some((name, EndgameResult::Score(score))) => {
+                    println!("endgame: {}, score {} (white)", name, score)
+                }
+                Some((name, EndgameResult::Scale(factor))) => {
+                    println!("endgame: {}, scaled by {}/{}", name, factor, SCALE_NORMAL)
+                }
+                None => (),
+            }
+
+            board.set_network(options.active_network());
+
+            if board.nnue.is_some() {
+                println!(
+                    "nnue: {} (side to move)",
+                    evaluate(
+                        &board,
+                        &mut PawnHashTable::new(DEFAULT_PAWN_HASH_SIZE_MB),
+                        &options.eval_params
+                    )
+                );
+            }
+        }
+        "tune" => {
+            let path = path.unwrap_or_else(|| {
+                println!("Missing tuning positions file!");
+                exit(1);
+            });
+
+            let output = output.unwrap_or(DEFAULT_TUNE_OUTPUT.to_string());
+
+            tune(path, iterations, &output, &options.eval_params);
+        }
+        "epd" => {
+            let path = path.unwrap_or_else(|| {
+                println!("Missing EPD file!");
+                exit(1);
+            });
+
+            if limits.infinite {
+                println!("Test suites can't be searched with --infinite!");
+                exit(1);
+            }
+
+            run_epd(path, limits, &options);
+        }
+        "params" => {
+            let path = path.unwrap_or_else(|| {
+                println!("Missing eval params file!");
+                exit(1);
+            });
+
+            options.eval_params.save(path).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+        }
+        "book" => {
+            let path = path.unwrap_or_else(|| {
+                println!("Missing book file!");
+                exit(1);
+            });
+            let board = BoardState::from_fen(&fen).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+            let book = Book::load(path).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            let key = polyglot_key(&board);
+            let entries = book.probe(key);
+            let total: u32 = entries.iter().map(|entry| entry.weight as u32).sum();
+
+            println!("key: {:016x}, entries: {}", key, entries.len());
+
+            for entry in entries {
+                println!(
+                    "{} weight {} ({:.1}%) learn {}",
+                    book_move_to_string(entry.raw_move),
+                    entry.weight,
+                    100.0 * entry.weight as f64 / total.max(1) as f64,
+                    entry.learn
+                );
+            }
+        }
+        "pgn" => {
+            let path = path.unwrap_or_else(|| {
+                println!("Missing PGN file!");
+                exit(1);
+            });
+            let games = load_pgn(path).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            for (number, game) in games.iter().enumerate() {
+                let players = format!(
+                    "{} - {}",
+                    game.tag("White").unwrap_or("?"),
+                    game.tag("Black").unwrap_or("?")
+                );
+
+                match game.replay() {
+                    Ok(replayed) => println!(
+                        "game {}: {}, {}, {} plies",
+                        number + 1,
+                        players,
+                        game.result,
+                        replayed.moves.len()
+                    ),
+                    Err(err) => println!("game {}: {}, {}", number + 1, players, err),
+                }
+            }
+        }
+        "selfplay" => {
+            if limits.infinite {
+                println!("Self-play games can't be searched with --infinite!");
+                exit(1);
+            }
+
+            let board = BoardState::from_fen(&fen).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            let game = self_play(
+                &board,
+                limits,
+                &options,
+                plies.unwrap_or(DEFAULT_SELFPLAY_PLIES),
+            );
+            let header = PgnHeader {
+                event: "ravissant self-play".to_string(),
+                white: "ravissant".to_string(),
+                black: "ravissant".to_string(),
+                result: game.result.clone(),
+                ..PgnHeader::default()
+            };
+            let text = write_pgn(&header, &game.start, &game.moves, Some(&game.evals))
+                .unwrap_or_else(|err| {
+                    println!("{}", err);
+                    exit(1);
+                });
 
-    let score = negamax(&mut board, 3);
+            match output {
+                Some(path) => fs::write(&path, text).unwrap_or_else(|err| {
+                    println!("{}", err);
+                    exit(1);
+                }),
+                None => print!("{}", text),
+            }
+        }
+        "makebook" => {
+            let path = path.unwrap_or_else(|| {
+                println!("Missing PGN file!");
+                exit(1);
+            });
+            let output = output.unwrap_or(DEFAULT_BOOK_OUTPUT.to_string());
 
-    println!("{}", score);
+            make_book(path, &output, &book_filter);
+        }
+        "bench" => bench(limits.depth.unwrap_or(DEFAULT_DEPTH), &options),
+        _ => {
+            println!("Unknown command!");
+            exit(1);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,101 @@
+use crate::{
+    board::{Bitboard, BoardState, Color, Piece},
+    constants::{BISHOP_MAGICS, KNIGHT_ATTACKS, PAWN_ATTACKS, ROOK_MAGICS},
+    eval_params::EvalParams,
+    magic::{get_bishop_move, get_rook_move},
+    piece_parsing::parse_bitboards,
+    utils::opposite_color,
+};
+
+// indexed by the number of safe squares, a piece with only a few squares is
+// much worse off than one with many, while the last few squares barely matter
+pub const KNIGHT_MOBILITY_MIDDLEGAME: [i32; 9] = [-31, -26, -6, -2, 2, 6, 11, 14, 16];
+pub const KNIGHT_MOBILITY_ENDGAME: [i32; 9] = [-40, -28, -15, -7, 4, 7, 11, 13, 16];
+
+pub const BISHOP_MOBILITY_MIDDLEGAME: [i32; 14] =
+    [-24, -10, 8, 13, 19, 25, 27, 31, 31, 34, 40, 40, 45, 49];
+pub const BISHOP_MOBILITY_ENDGAME: [i32; 14] =
+    [-29, -11, -1, 6, 12, 21, 27, 28, 32, 36, 39, 43, 44, 48];
+
+pub const ROOK_MOBILITY_MIDDLEGAME: [i32; 15] =
+    [-29, -13, -7, -5, -2, -1, 4, 8, 15, 14, 16, 19, 23, 24, 29];
+pub const ROOK_MOBILITY_ENDGAME: [i32; 15] =
+    [-38, -9, 14, 27, 34, 41, 56, 59, 66, 71, 77, 82, 83, 84, 85];
+
+pub const QUEEN_MOBILITY_MIDDLEGAME: [i32; 28] = [
+    -19, -10, 1, 1, 7, 11, 14, 20, 21, 24, 28, 30, 30, 33, 33, 35, 35, 36, 39, 44, 44, 49, 51, 51,
+    53, 54, 56, 58,
+];
+pub const QUEEN_MOBILITY_ENDGAME: [i32; 28] = [
+    -18, -7, 4, 9, 17, 27, 30, 36, 39, 46, 47, 52, 56, 60, 61, 63, 66, 68, 70, 71, 74, 83, 85, 87,
+    92, 95, 103, 106,
+];
+
+// squares attacked by the pawns of the color
+pub fn pawn_attacks(board: &BoardState, color: Color) -> Bitboard {
+    parse_bitboards(color, board.get_piece_bb(color, Piece::Pawn))
+        .iter()
+        .fold(0, |bb_attacks, pawn| {
+            bb_attacks | PAWN_ATTACKS[color as usize][pawn.1 as usize]
+        })
+}
+
+// squares attacked by a knight, bishop, rook or queen on the square
+pub fn piece_attacks(piece: Piece, square: usize, bb_occupancy: Bitboard) -> Bitboard {
+    match piece {
+        Piece::Knight => KNIGHT_ATTACKS[square],
+        Piece::Bishop => get_bishop_move(BISHOP_MAGICS[square], bb_occupancy),
+        Piece::Rook => get_rook_move(ROOK_MAGICS[square], bb_occupancy),
+        Piece::Queen => {
+            get_bishop_move(BISHOP_MAGICS[square], bb_occupancy)
+                | get_rook_move(ROOK_MAGICS[square], bb_occupancy)
+        }
+        _ => 0,
+    }
+}
+
+// knights, bishops, rooks and queens of one color, (middlegame, endgame).
+// squares with friendly pieces or attacked by enemy pawns don't count
+pub fn mobility_score(board: &BoardState, color: Color, params: &EvalParams) -> (i32, i32) {
+    let bb_mobility_area =
+        !board.bb_colors[color as usize] & !pawn_attacks(board, opposite_color(color));
+
+    let mut middlegame_score = 0;
+    let mut endgame_score = 0;
+
+    for (piece, middlegame_table, endgame_table) in [
+        (
+            Piece::Knight,
+            &params.knight_mobility_middlegame[..],
+            &params.knight_mobility_endgame[..],
+        ),
+        (
+            Piece::Bishop,
+            &params.bishop_mobility_middlegame[..],
+            &params.bishop_mobility_endgame[..],
+        ),
+        (
+            Piece::Rook,
+            &params.rook_mobility_middlegame[..],
+            &params.rook_mobility_endgame[..],
+        ),
+        (
+            Piece::Queen,
+            &params.queen_mobility_middlegame[..],
+            &params.queen_mobility_endgame[..],
+        ),
+    ] {
+        for square in parse_bitboards(color, board.get_piece_bb(color, piece)).iter() {
+            let square = square.1 as usize;
+
+            let bb_attacks = piece_attacks(piece, square, board.bb_fullboard);
+
+            let mobility = (bb_attacks & bb_mobility_area).count_ones() as usize;
+
+            middlegame_score += middlegame_table[mobility];
+            endgame_score += endgame_table[mobility];
+        }
+    }
+
+    (middlegame_score, endgame_score)
+}
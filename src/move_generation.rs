@@ -31,7 +31,10 @@ pub fn generate_pawn_moves(
 
             // if pawn is on 2nd rank
             if pawn.1 >= 48 && pawn.1 <= 55 {
-                if (BOARD_SQUARES[pawn.1 as usize] >> 16) | bb_fullboard != bb_fullboard {
+                // the square in between has to be empty too
+                if (BOARD_SQUARES[pawn.1 as usize] >> 16) | bb_fullboard != bb_fullboard
+                    && (BOARD_SQUARES[pawn.1 as usize] >> 8) & bb_fullboard == 0
+                {
                     bb_pawn_moves |= BOARD_SQUARES[pawn.1 as usize] >> 16;
                 }
             }
@@ -51,7 +54,10 @@ pub fn generate_pawn_moves(
 
             // if pawn is on 7th rank
             if pawn.1 >= 8 && pawn.1 <= 15 {
-                if (BOARD_SQUARES[pawn.1 as usize] << 16) | bb_fullboard != bb_fullboard {
+                // the square in between has to be empty too
+                if (BOARD_SQUARES[pawn.1 as usize] << 16) | bb_fullboard != bb_fullboard
+                    && (BOARD_SQUARES[pawn.1 as usize] << 8) & bb_fullboard == 0
+                {
                     bb_pawn_moves |= BOARD_SQUARES[pawn.1 as usize] << 16;
                 }
             }
@@ -0,0 +1,360 @@
+use crate::{
+    board::{BoardState, Color, Piece},
+    piece_parsing::parse_bitboards,
+    utils::{number_to_piece, opposite_color},
+};
+use std::{fs, process::exit, sync::Arc};
+
+// weight file, everything little endian:
+//   magic         4 bytes "RVNN"
+//   version       u32, NNUE_VERSION
+//   hidden size   u32, h
+//   feature weights  i16 x 768 * h, h weights for every input in input order
+//   feature biases   i16 x h
+//   output weights   i16 x 2 * h, side to move half first and then the other side
+//   output bias      i32
+//
+// the network is 768 -> h x 2 -> 1. inputs are (color * 6 + piece) * 64 + square from the
+// point of view of the side the accumulator belongs to, colors swapped and the board flipped
+// for black, so both halves share the feature weights. hidden values are clipped to
+// 0..NNUE_QA, feature weights are quantized by NNUE_QA and output weights by NNUE_QB
+pub const NNUE_MAGIC: &[u8; 4] = b"RVNN";
+pub const NNUE_VERSION: u32 = 1;
+pub const NNUE_INPUTS: usize = 768;
+pub const NNUE_QA: i32 = 255;
+pub const NNUE_QB: i32 = 64;
+// centipawns per unit of network output
+pub const NNUE_SCALE: i32 = 400;
+
+#[derive(Debug)]
+pub struct Network {
+    pub hidden: usize,
+    pub feature_weights: Vec<i16>,
+    pub feature_biases: Vec<i16>,
+    pub output_weights: Vec<i16>,
+    pub output_bias: i32,
+}
+
+// hidden layer values from the point of view of each color, kept up to date by make_move
+// and undo_move while a network is set on the board
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    pub values: [Vec<i16>; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct NnueState {
+    pub network: Arc<Network>,
+    pub accumulator: Accumulator,
+}
+
+pub fn feature_index(perspective: Color, color: Color, piece: Piece, square: usize) -> usize {
+    match perspective {
+        Color::White => (color as usize * 6 + piece as usize) * 64 + square,
+        // a8 is 0, so flipping the row mirrors the board
+        Color::Black => ((1 - color as usize) * 6 + piece as usize) * 64 + (square ^ 56),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32, &'static str> {
+    let value = bytes
+        .get(*offset..*offset + 4)
+        .ok_or("Unexpected end of the network file!")?;
+
+    *offset += 4;
+
+    Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+fn read_i16s(bytes: &[u8], offset: &mut usize, count: usize) -> Result<Vec<i16>, &'static str> {
+    let values = bytes
+        .get(*offset..*offset + count * 2)
+        .ok_or("Unexpected end of the network file!")?;
+
+    *offset += count * 2;
+
+    Ok(values
+        .chunks(2)
+        .map(|value| i16::from_le_bytes([value[0], value[1]]))
+        .collect())
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, &'static str> {
+        if bytes.get(..4) != Some(&NNUE_MAGIC[..]) {
+            return Err("Not a network file!");
+        }
+
+        let mut offset = 4;
+
+        if read_u32(bytes, &mut offset)? != NNUE_VERSION {
+            return Err("Unsupported network version!");
+        }
+
+        let hidden = read_u32(bytes, &mut offset)? as usize;
+
+        if hidden == 0 || hidden > 1 << 16 {
+            return Err("Incorrect network hidden size!");
+        }
+
+        let network = Network {
+            hidden,
+            feature_weights: read_i16s(bytes, &mut offset, NNUE_INPUTS * hidden)?,
+            feature_biases: read_i16s(bytes, &mut offset, hidden)?,
+            output_weights: read_i16s(bytes, &mut offset, 2 * hidden)?,
+            output_bias: read_u32(bytes, &mut offset)? as i32,
+        };
+
+        if offset != bytes.len() {
+            return Err("Unexpected data at the end of the network file!");
+        }
+
+        Ok(network)
+    }
+
+    pub fn load(path: &str) -> Result<Network, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "Can't read the network file!")?;
+
+        Network::from_bytes(&bytes)
+    }
+
+    fn feature_weights(&self, index: usize) -> &[i16] {
+        &self.feature_weights[index * self.hidden..(index + 1) * self.hidden]
+    }
+
+    // score in centipawns for the side to move
+    pub fn evaluate(&self, accumulator: &Accumulator, to_move: Color) -> i32 {
+        let (us, them) = (
+            &accumulator.values[to_move as usize],
+            &accumulator.values[1 - to_move as usize],
+        );
+
+        let output = clipped_dot(us, &self.output_weights[..self.hidden])
+            + clipped_dot(them, &self.output_weights[self.hidden..])
+            + self.output_bias;
+
+        output * NNUE_SCALE / (NNUE_QA * NNUE_QB)
+    }
+}
+
+impl Accumulator {
+    // full recomputation from the pieces on the board
+    pub fn new(network: &Network, board: &BoardState) -> Accumulator {
+        let mut accumulator = Accumulator {
+            values: [
+                network.feature_biases.clone(),
+                network.feature_biases.clone(),
+            ],
+        };
+
+        for color in [Color::White, Color::Black] {
+            for index in 0..6 {
+                let piece = number_to_piece(index).unwrap_or_else(|err| {
+                    println!("{}", err);
+                    exit(1);
+                });
+
+                for square in parse_bitboards(color, board.get_piece_bb(color, piece)).iter() {
+                    accumulator.add(network, color, piece, square.1 as usize);
+                }
+            }
+        }
+
+        accumulator
+    }
+
+    pub fn add(&mut self, network: &Network, color: Color, piece: Piece, square: usize) {
+        for perspective in [Color::White, Color::Black] {
+            let weights = network.feature_weights(feature_index(perspective, color, piece, square));
+
+            add_assign(&mut self.values[perspective as usize], weights);
+        }
+    }
+
+    pub fn remove(&mut self, network: &Network, color: Color, piece: Piece, square: usize) {
+        for perspective in [Color::White, Color::Black] {
+            let weights = network.feature_weights(feature_index(perspective, color, piece, square));
+
+            sub_assign(&mut self.values[perspective as usize], weights);
+        }
+    }
+}
+
+impl NnueState {
+    pub fn new(network: Arc<Network>, board: &BoardState) -> NnueState {
+        NnueState {
+            accumulator: Accumulator::new(&network, board),
+            network,
+        }
+    }
+
+    // piece moved from one square to another, capturing on the destination square
+    pub fn make_move(
+        &mut self,
+        color: Color,
+        piece: Piece,
+        from: usize,
+        to: usize,
+        captured: Piece,
+    ) {
+        self.accumulator.remove(&self.network, color, piece, from);
+        self.accumulator.add(&self.network, color, piece, to);
+
+        if !matches!(captured, Piece::None) {
+            self.accumulator
+                .remove(&self.network, opposite_color(color), captured, to);
+        }
+    }
+
+    // a piece appearing or leaving without a move, like a promotion or an en passant capture
+    pub fn add_piece(&mut self, color: Color, piece: Piece, square: usize) {
+        self.accumulator.add(&self.network, color, piece, square);
+    }
+
+    pub fn remove_piece(&mut self, color: Color, piece: Piece, square: usize) {
+        self.accumulator.remove(&self.network, color, piece, square);
+    }
+
+    pub fn undo_move(
+        &mut self,
+        color: Color,
+        piece: Piece,
+        from: usize,
+        to: usize,
+        captured: Piece,
+    ) {
+        self.accumulator.remove(&self.network, color, piece, to);
+        self.accumulator.add(&self.network, color, piece, from);
+
+        if !matches!(captured, Piece::None) {
+            self.accumulator
+                .add(&self.network, opposite_color(color), captured, to);
+        }
+    }
+}
+
+// the simd versions need the hidden size to be a multiple of 16 i16 lanes
+fn use_avx2(length: usize) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        length.is_multiple_of(16) && is_x86_feature_detected!("avx2")
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = length;
+        false
+    }
+}
+
+fn add_assign(values: &mut [i16], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if use_avx2(values.len()) {
+        // safe, avx2 support was checked and both slices have the same length
+        unsafe { simd::add_assign(values, weights) };
+        return;
+    }
+
+    add_assign_scalar(values, weights);
+}
+
+fn sub_assign(values: &mut [i16], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if use_avx2(values.len()) {
+        // safe, avx2 support was checked and both slices have the same length
+        unsafe { simd::sub_assign(values, weights) };
+        return;
+    }
+
+    sub_assign_scalar(values, weights);
+}
+
+// sum of the hidden values clipped to 0..NNUE_QA times the weights
+pub fn clipped_dot(values: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if use_avx2(values.len()) {
+        // safe, avx2 support was checked and both slices have the same length
+        return unsafe { simd::clipped_dot(values, weights) };
+    }
+
+    clipped_dot_scalar(values, weights)
+}
+
+pub fn add_assign_scalar(values: &mut [i16], weights: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(weights) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+pub fn sub_assign_scalar(values: &mut [i16], weights: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(weights) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+pub fn clipped_dot_scalar(values: &[i16], weights: &[i16]) -> i32 {
+    values
+        .iter()
+        .zip(weights)
+        .map(|(value, weight)| (*value as i32).clamp(0, NNUE_QA) * *weight as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use super::NNUE_QA;
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_assign(values: &mut [i16], weights: &[i16]) {
+        for (value, weight) in values.chunks_exact_mut(16).zip(weights.chunks_exact(16)) {
+            let sum = _mm256_add_epi16(
+                _mm256_loadu_si256(value.as_ptr() as *const __m256i),
+                _mm256_loadu_si256(weight.as_ptr() as *const __m256i),
+            );
+
+            _mm256_storeu_si256(value.as_mut_ptr() as *mut __m256i, sum);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_assign(values: &mut [i16], weights: &[i16]) {
+        for (value, weight) in values.chunks_exact_mut(16).zip(weights.chunks_exact(16)) {
+            let difference = _mm256_sub_epi16(
+                _mm256_loadu_si256(value.as_ptr() as *const __m256i),
+                _mm256_loadu_si256(weight.as_ptr() as *const __m256i),
+            );
+
+            _mm256_storeu_si256(value.as_mut_ptr() as *mut __m256i, difference);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn clipped_dot(values: &[i16], weights: &[i16]) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(NNUE_QA as i16);
+        let mut sum = _mm256_setzero_si256();
+
+        for (value, weight) in values.chunks_exact(16).zip(weights.chunks_exact(16)) {
+            let clipped = _mm256_min_epi16(
+                _mm256_max_epi16(_mm256_loadu_si256(value.as_ptr() as *const __m256i), zero),
+                max,
+            );
+
+            // pairs of i16 products added into i32 lanes
+            sum = _mm256_add_epi32(
+                sum,
+                _mm256_madd_epi16(
+                    clipped,
+                    _mm256_loadu_si256(weight.as_ptr() as *const __m256i),
+                ),
+            );
+        }
+
+        let mut lanes = [0i32; 8];
+
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+
+        lanes.iter().sum()
+    }
+}
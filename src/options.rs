@@ -0,0 +1,100 @@
+use crate::{
+    board::{BoardState, EncodedMove},
+    eval_params::EvalParams,
+    nnue::Network,
+    polyglot::{Book, BookMode},
+    tt::DEFAULT_TT_SIZE_MB,
+};
+use std::sync::Arc;
+
+pub const MAX_THREADS: usize = 256;
+pub const MAX_MULTIPV: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct EngineOptions {
+    pub threads: usize,
+    pub hash_mb: usize,
+    pub multipv: usize,
+    // weights loaded with the EvalFile option, defaults otherwise
+    pub eval_params: EvalParams,
+    // network loaded with the NNUEFile option, only used with UseNNUE
+    pub network: Option<Arc<Network>>,
+    pub use_nnue: bool,
+    // polyglot book from the Book option, only played from with OwnBook
+    pub book: Option<Arc<Book>>,
+    pub own_book: bool,
+    pub book_mode: BookMode,
+}
+
+impl Default for EngineOptions {
+    fn default() -> EngineOptions {
+        EngineOptions {
+            threads: 1,
+            hash_mb: DEFAULT_TT_SIZE_MB,
+            multipv: 1,
+            eval_params: EvalParams::default(),
+            network: None,
+            use_nnue: false,
+            book: None,
+            own_book: false,
+            book_mode: BookMode::Random,
+        }
+    }
+}
+
+impl EngineOptions {
+    // option names are case insensitive like in uci
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        match name.to_lowercase().as_str() {
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) if (1..=MAX_THREADS).contains(&threads) => self.threads = threads,
+                _ => return Err("Incorrect Threads value!"),
+            },
+            "hash" => match value.parse::<usize>() {
+                Ok(hash_mb) if hash_mb > 0 => self.hash_mb = hash_mb,
+                _ => return Err("Incorrect Hash value!"),
+            },
+            "multipv" => match value.parse::<usize>() {
+                Ok(multipv) if (1..=MAX_MULTIPV).contains(&multipv) => self.multipv = multipv,
+                _ => return Err("Incorrect MultiPV value!"),
+            },
+            "evalfile" => self.eval_params = EvalParams::load(value)?,
+            "nnuefile" => self.network = Some(Arc::new(Network::load(value)?)),
+            "usennue" => match value.to_lowercase().as_str() {
+                "true" => self.use_nnue = true,
+                "false" => self.use_nnue = false,
+                _ => return Err("Incorrect UseNNUE value!"),
+            },
+            "book" => self.book = Some(Arc::new(Book::load(value)?)),
+            "ownbook" => match value.to_lowercase().as_str() {
+                "true" => self.own_book = true,
+                "false" => self.own_book = false,
+                _ => return Err("Incorrect OwnBook value!"),
+            },
+            "bookmode" => match value.to_lowercase().as_str() {
+                "best" => self.book_mode = BookMode::Best,
+                "random" => self.book_mode = BookMode::Random,
+                _ => return Err("Incorrect BookMode value!"),
+            },
+            _ => return Err("Unknown option!"),
+        }
+
+        Ok(())
+    }
+
+    // book move for the position when playing from the book
+    pub fn book_move(&self, board: &BoardState) -> Option<EncodedMove> {
+        match (&self.book, self.own_book) {
+            (Some(book), true) => book.pick_move(board, self.book_mode),
+            _ => None,
+        }
+    }
+
+    // network the search boards should use, none for the classical eval
+    pub fn active_network(&self) -> Option<Arc<Network>> {
+        match self.use_nnue {
+            true => self.network.clone(),
+            false => None,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{
+    board::{Bitboard, BoardState, Color},
+    eval_params::EvalParams,
+    pawn_structure::{passed_pawns, pawn_only_score},
+};
+
+pub const DEFAULT_PAWN_HASH_SIZE_MB: usize = 1;
+
+// everything here only depends on the pawns, so it can be reused for every
+// position with the same pawn key
+#[derive(Debug, Copy, Clone)]
+pub struct PawnEntry {
+    pub key: u64,
+    // (middlegame, endgame) by color
+    pub scores: [(i32, i32); 2],
+    pub bb_passed: [Bitboard; 2],
+}
+
+// every search thread has its own table, so there is nothing to share
+pub struct PawnHashTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnHashTable {
+    pub fn new(size_mb: usize) -> PawnHashTable {
+        let entries_count =
+            (size_mb * 1024 * 1024 / std::mem::size_of::<Option<PawnEntry>>()).max(1);
+
+        PawnHashTable {
+            entries: vec![None; entries_count],
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        self.entries[(key % self.entries.len() as u64) as usize].filter(|entry| entry.key == key)
+    }
+
+    // cached scores are only right for the params they were computed with
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = (entry.key % self.entries.len() as u64) as usize;
+
+        self.entries[index] = Some(entry);
+    }
+
+    // cached entry of the board's pawns, computed and stored on a miss
+    pub fn get(&mut self, board: &BoardState, params: &EvalParams) -> PawnEntry {
+        if let Some(entry) = self.probe(board.pawn_key) {
+            return entry;
+        }
+
+        let entry = PawnEntry {
+            key: board.pawn_key,
+            scores: [
+                pawn_only_score(board, Color::White, params),
+                pawn_only_score(board, Color::Black, params),
+            ],
+            bb_passed: [
+                passed_pawns(board, Color::White),
+                passed_pawns(board, Color::Black),
+            ],
+        };
+
+        self.store(entry);
+
+        entry
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    board::{Bitboard, BoardState, Color, Piece},
+    constants::{
+        A_FILE, BOARD_SQUARES, B_FILE, C_FILE, D_FILE, EIGTH_RANK, E_FILE, FIFTH_RANK, FIRST_RANK,
+        FOURTH_RANK, F_FILE, G_FILE, H_FILE, PAWN_ATTACKS, SECOND_RANK, SEVENTH_RANK, SIXTH_RANK,
+        THIRD_RANK,
+    },
+    eval_params::EvalParams,
+    piece_parsing::parse_bitboards,
+    utils::opposite_color,
+};
+
+pub const FILES: [Bitboard; 8] = [
+    A_FILE, B_FILE, C_FILE, D_FILE, E_FILE, F_FILE, G_FILE, H_FILE,
+];
+
+// rows counted from the top of the board, FIRST_RANK holds a8..h8
+pub const RANKS: [Bitboard; 8] = [
+    FIRST_RANK,
+    SECOND_RANK,
+    THIRD_RANK,
+    FOURTH_RANK,
+    FIFTH_RANK,
+    SIXTH_RANK,
+    SEVENTH_RANK,
+    EIGTH_RANK,
+];
+
+// (middlegame, endgame) values
+pub const DOUBLED_PAWN_PENALTY: (i32, i32) = (-10, -20);
+pub const ISOLATED_PAWN_PENALTY: (i32, i32) = (-10, -15);
+pub const BACKWARD_PAWN_PENALTY: (i32, i32) = (-8, -10);
+// pawn with a friendly pawn next to it on the same rank
+pub const CONNECTED_PAWN_BONUS: (i32, i32) = (8, 5);
+// pawn defended by a friendly pawn
+pub const PAWN_CHAIN_BONUS: (i32, i32) = (10, 8);
+
+// by relative rank, passed pawns with a piece right in front of them get half
+pub const PASSED_PAWN_MIDDLEGAME_BONUS: [i32; 8] = [0, 5, 10, 15, 25, 40, 65, 0];
+pub const PASSED_PAWN_ENDGAME_BONUS: [i32; 8] = [0, 10, 20, 30, 50, 80, 120, 0];
+
+pub fn adjacent_files(file: usize) -> Bitboard {
+    let mut bb_files = 0;
+
+    if file > 0 {
+        bb_files |= FILES[file - 1];
+    }
+
+    if file < 7 {
+        bb_files |= FILES[file + 1];
+    }
+
+    bb_files
+}
+
+// rows in front of the row from the point of view of the color
+pub fn rows_ahead(color: Color, row: usize) -> Bitboard {
+    match color {
+        Color::White => RANKS[..row].iter().fold(0, |bb_rows, rank| bb_rows | rank),
+        Color::Black => RANKS[row + 1..]
+            .iter()
+            .fold(0, |bb_rows, rank| bb_rows | rank),
+    }
+}
+
+// 0 is the color's own back rank, 7 is the promotion rank
+pub fn relative_rank(color: Color, square: usize) -> usize {
+    match color {
+        Color::White => 7 - square / 8,
+        Color::Black => square / 8,
+    }
+}
+
+// square right in front of the pawn, none on the promotion rank
+pub fn stop_square(color: Color, square: usize) -> Option<usize> {
+    match color {
+        Color::White => square.checked_sub(8),
+        Color::Black => Some(square + 8).filter(|&stop| stop < 64),
+    }
+}
+
+// pawns without enemy pawns in front of them on their own or adjacent files
+pub fn passed_pawns(board: &BoardState, color: Color) -> Bitboard {
+    let bb_enemy_pawns = board.get_piece_bb(opposite_color(color), Piece::Pawn);
+
+    let mut bb_passed = 0;
+
+    for pawn in parse_bitboards(color, board.get_piece_bb(color, Piece::Pawn)).iter() {
+        let square = pawn.1 as usize;
+        let bb_front_span =
+            (FILES[square % 8] | adjacent_files(square % 8)) & rows_ahead(color, square / 8);
+
+        if bb_front_span & bb_enemy_pawns == 0 {
+            bb_passed |= BOARD_SQUARES[square];
+        }
+    }
+
+    bb_passed
+}
+
+// doubled, isolated, backward, connected and chained pawns of one color,
+// (middlegame, endgame). only depends on the pawns, so the pawn hash table can cache it
+pub fn pawn_only_score(board: &BoardState, color: Color, params: &EvalParams) -> (i32, i32) {
+    let bb_pawns = board.get_piece_bb(color, Piece::Pawn);
+    let bb_enemy_pawns = board.get_piece_bb(opposite_color(color), Piece::Pawn);
+
+    let mut middlegame_score = 0;
+    let mut endgame_score = 0;
+
+    let mut add = |(middlegame, endgame): (i32, i32)| {
+        middlegame_score += middlegame;
+        endgame_score += endgame;
+    };
+
+    // every pawn after the first one on a file is doubled
+    for file in FILES.iter() {
+        let pawns_on_file = (bb_pawns & file).count_ones() as i32;
+
+        if pawns_on_file > 1 {
+            add((
+                params.doubled_pawn.0 * (pawns_on_file - 1),
+                params.doubled_pawn.1 * (pawns_on_file - 1),
+            ));
+        }
+    }
+
+    for pawn in parse_bitboards(color, bb_pawns).iter() {
+        let square = pawn.1 as usize;
+        let row = square / 8;
+        let bb_adjacent_pawns = bb_pawns & adjacent_files(square % 8);
+
+        if bb_adjacent_pawns == 0 {
+            add(params.isolated_pawn);
+        } else {
+            // all neighbours are ahead of it and it can't advance safely
+            let stop_attacked = match stop_square(color, square) {
+                Some(stop) => PAWN_ATTACKS[color as usize][stop] & bb_enemy_pawns != 0,
+                None => false,
+            };
+
+            if bb_adjacent_pawns & !rows_ahead(color, row) == 0 && stop_attacked {
+                add(params.backward_pawn);
+            }
+        }
+
+        if bb_adjacent_pawns & RANKS[row] != 0 {
+            add(params.connected_pawn);
+        }
+
+        // friendly pawns that attack this square defend it
+        if PAWN_ATTACKS[opposite_color(color) as usize][square] & bb_pawns != 0 {
+            add(params.pawn_chain);
+        }
+    }
+
+    (middlegame_score, endgame_score)
+}
+
+// passed pawns depend on the pieces blocking them, so this part is never cached
+pub fn passed_pawn_score(
+    board: &BoardState,
+    color: Color,
+    bb_passed: Bitboard,
+    params: &EvalParams,
+) -> (i32, i32) {
+    let mut middlegame_score = 0;
+    let mut endgame_score = 0;
+
+    for pawn in parse_bitboards(color, bb_passed).iter() {
+        let square = pawn.1 as usize;
+        let rank = relative_rank(color, square);
+        let blocked = match stop_square(color, square) {
+            Some(stop) => board.bb_fullboard & BOARD_SQUARES[stop] != 0,
+            None => false,
+        };
+        let scale = if blocked { 2 } else { 1 };
+
+        middlegame_score += params.passed_pawn_middlegame[rank] / scale;
+        endgame_score += params.passed_pawn_endgame[rank] / scale;
+    }
+
+    (middlegame_score, endgame_score)
+}
@@ -0,0 +1,582 @@
+use crate::{
+    board::{BoardState, Color, EncodedMove},
+    constants::DEFAULT_FEN_STRING,
+    eval::CHECKMATE,
+    san::{move_to_san, parse_san},
+    search::MAX_PLY,
+};
+use std::{
+    fmt, fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const GAME_RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+// move suffixes and the nags they stand for
+const SUFFIX_NAGS: [(&str, u32); 6] = [
+    ("!", 1),
+    ("?", 2),
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    // $n annotations, move suffixes like ! are turned into them
+    pub nags: Vec<u32>,
+    pub comment: Option<String>,
+    // alternatives to this move, every one starts with its own first move
+    pub variations: Vec<Vec<PgnMove>>,
+    // line of the move in the pgn text
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    // comment before the first move
+    pub comment: Option<String>,
+    // main line
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+    // line the game starts on in the pgn text
+    pub line: usize,
+}
+
+// every position of the main line, positions[i] is the one moves[i] is played in and the
+// last one is the final position
+#[derive(Debug, Clone)]
+pub struct ReplayedGame {
+    pub positions: Vec<BoardState>,
+    pub moves: Vec<EncodedMove>,
+}
+
+// seven tag roster of exported games, unknown values are ?
+#[derive(Debug, Clone)]
+pub struct PgnHeader {
+    pub event: String,
+    pub site: String,
+    // YYYY.MM.DD
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+    pub result: String,
+}
+
+// search result of a move, from the side playing it like the search gives it. the pgn
+// comments turn it to white's point of view
+#[derive(Debug, Clone, Copy)]
+pub struct MoveEval {
+    pub score: i32,
+    pub depth: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnError {
+    pub line: usize,
+    // the offending move with its number like 12... Nf3, or the offending text
+    pub context: String,
+    pub message: &'static str,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, {}: {}", self.line, self.context, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u32),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+    Move(String),
+}
+
+fn error(line: usize, context: &str, message: &'static str) -> PgnError {
+    PgnError {
+        line,
+        context: context.to_string(),
+        message,
+    }
+}
+
+// [Name "value"], quotes and backslashes in the value are escaped with a backslash
+fn parse_tag(text: &str) -> Option<(String, String)> {
+    let inner = text.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((
+        name.to_string(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+// tokens with the line they start on
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, PgnError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        let token_line = line;
+        let first_column = line_start;
+
+        line_start = false;
+
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = true;
+                continue;
+            }
+            // escaped lines are for other programs, only with % in the first column
+            '%' if first_column => {
+                chars.by_ref().find(|&c| c == '\n');
+                line += 1;
+                line_start = true;
+                continue;
+            }
+            _ if c.is_whitespace() => continue,
+            '[' => {
+                let mut text = String::from('[');
+                let mut in_string = false;
+
+                loop {
+                    let Some(c) = chars.next() else {
+                        return Err(error(token_line, &text, "Unclosed PGN tag!"));
+                    };
+
+                    text.push(c);
+
+                    match c {
+                        '\\' if in_string => text.extend(chars.next()),
+                        '"' => in_string = !in_string,
+                        ']' if !in_string => break,
+                        '\n' => return Err(error(token_line, &text, "Unclosed PGN tag!")),
+                        _ => (),
+                    }
+                }
+
+                let (name, value) = parse_tag(&text)
+                    .ok_or_else(|| error(token_line, &text, "Incorrect PGN tag!"))?;
+
+                tokens.push((Token::Tag(name, value), token_line));
+            }
+            '{' => {
+                let mut comment = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+
+                            comment.push(c);
+                        }
+                        None => return Err(error(token_line, "{", "Unclosed PGN comment!")),
+                    }
+                }
+
+                tokens.push((Token::Comment(comment.trim().to_string()), token_line));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+
+                tokens.push((Token::Comment(comment.trim().to_string()), token_line));
+                line += 1;
+                line_start = true;
+                continue;
+            }
+            '(' => tokens.push((Token::VariationStart, token_line)),
+            ')' => tokens.push((Token::VariationEnd, token_line)),
+            _ => {
+                let mut text = String::from(c);
+
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "{}()[];".contains(next) {
+                        break;
+                    }
+
+                    text.push(next);
+                    chars.next();
+                }
+
+                if let Some(nag) = text.strip_prefix('$') {
+                    let nag = nag
+                        .parse()
+                        .map_err(|_| error(token_line, &text, "Incorrect PGN annotation!"))?;
+
+                    tokens.push((Token::Nag(nag), token_line));
+                } else if GAME_RESULTS.contains(&text.as_str()) {
+                    tokens.push((Token::Result(text), token_line));
+                } else {
+                    // move numbers may stick to the move like 12.e4 or 12...e5. only digits
+                    // followed by dots are a number, so 0-0 and 1.0-0 keep their castling
+                    let number = text.trim_start_matches(|c: char| c.is_ascii_digit());
+                    let san = match number.is_empty() || number.starts_with('.') {
+                        true => number.trim_start_matches('.'),
+                        false => text.as_str(),
+                    };
+                    let annotation = san.trim_start_matches(|c| c != '!' && c != '?');
+                    let san = &san[..san.len() - annotation.len()];
+
+                    if !san.is_empty() {
+                        tokens.push((Token::Move(san.to_string()), token_line));
+                    }
+
+                    if !annotation.is_empty() {
+                        let (_, nag) = SUFFIX_NAGS
+                            .iter()
+                            .find(|(suffix, _)| *suffix == annotation)
+                            .ok_or_else(|| {
+                                error(token_line, &text, "Incorrect PGN move annotation!")
+                            })?;
+
+                        tokens.push((Token::Nag(*nag), token_line));
+                    }
+                }
+            }
+        }
+
+        line_start = false;
+    }
+
+    Ok(tokens)
+}
+
+// moves until the end of the variation, or of the game for the main line
+fn parse_line(
+    tokens: &[(Token, usize)],
+    index: &mut usize,
+    nested: bool,
+    leading_comment: &mut Option<String>,
+) -> Result<Vec<PgnMove>, PgnError> {
+    let mut moves: Vec<PgnMove> = vec![];
+
+    while let Some((token, line)) = tokens.get(*index) {
+        match token {
+            Token::Tag(_, _) | Token::Result(_) if nested => {
+                return Err(error(*line, "(", "Unclosed PGN variation!"));
+            }
+            Token::Tag(_, _) | Token::Result(_) => break,
+            Token::VariationEnd if nested => {
+                *index += 1;
+                return Ok(moves);
+            }
+            Token::VariationEnd => return Err(error(*line, ")", "Unexpected end of variation!")),
+            Token::VariationStart => {
+                *index += 1;
+
+                let variation = parse_line(tokens, index, true, &mut None)?;
+                let last_move = moves
+                    .last_mut()
+                    .ok_or_else(|| error(*line, "(", "Variation before any move!"))?;
+
+                last_move.variations.push(variation);
+                continue;
+            }
+            Token::Comment(comment) => {
+                let target = match moves.last_mut() {
+                    Some(last_move) => &mut last_move.comment,
+                    None => &mut *leading_comment,
+                };
+
+                *target = Some(match target.take() {
+                    Some(previous) => format!("{} {}", previous, comment),
+                    None => comment.clone(),
+                });
+            }
+            Token::Nag(nag) => {
+                if let Some(last_move) = moves.last_mut() {
+                    last_move.nags.push(*nag);
+                }
+            }
+            Token::Move(san) => moves.push(PgnMove {
+                san: san.clone(),
+                nags: vec![],
+                comment: None,
+                variations: vec![],
+                line: *line,
+            }),
+        }
+
+        *index += 1;
+    }
+
+    match nested {
+        true => Err(error(
+            tokens.last().map_or(1, |(_, line)| *line),
+            "(",
+            "Unclosed PGN variation!",
+        )),
+        false => Ok(moves),
+    }
+}
+
+// every game of a pgn collection
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(text)?;
+    let mut games = vec![];
+    let mut index = 0;
+
+    while let Some((_, line)) = tokens.get(index) {
+        let mut game = PgnGame {
+            tags: vec![],
+            comment: None,
+            moves: vec![],
+            result: "*".to_string(),
+            line: *line,
+        };
+
+        while let Some((Token::Tag(name, value), _)) = tokens.get(index) {
+            game.tags.push((name.clone(), value.clone()));
+            index += 1;
+        }
+
+        game.moves = parse_line(&tokens, &mut index, false, &mut game.comment)?;
+
+        if let Some((Token::Result(result), _)) = tokens.get(index) {
+            game.result = result.clone();
+            index += 1;
+        }
+
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+pub fn load_pgn(path: &str) -> Result<Vec<PgnGame>, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+    parse_pgn(&text).map_err(|err| err.to_string())
+}
+
+// move number like 12. for white and 12... for black
+pub fn move_number(board: &BoardState) -> String {
+    match board.to_move {
+        Color::White => format!("{}.", board.fullmove),
+        Color::Black => format!("{}...", board.fullmove),
+    }
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // the FEN tag or the standard starting position. the board keeps no halfmove clock, so
+    // it starts from 0 like with epd and tuning positions
+    pub fn start_board(&self) -> Result<BoardState, PgnError> {
+        let fen = self.tag("FEN").unwrap_or(DEFAULT_FEN_STRING);
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+
+        if fields.len() == 6 {
+            fields[4] = "0";
+        }
+
+        BoardState::from_fen(&fields.join(" ")).map_err(|message| error(self.line, fen, message))
+    }
+
+    // main line moves played on the board as far as they go, with the error that stopped it
+    pub fn replay_partial(&self) -> (ReplayedGame, Option<PgnError>) {
+        let mut board = match self.start_board() {
+            Ok(board) => board,
+            Err(err) => {
+                let replayed = ReplayedGame {
+                    positions: vec![],
+                    moves: vec![],
+                };
+
+                return (replayed, Some(err));
+            }
+        };
+        let mut replayed = ReplayedGame {
+            positions: vec![board.clone()],
+            moves: vec![],
+        };
+
+        for pgn_move in self.moves.iter() {
+            let piece_move = match parse_san(&mut board, &pgn_move.san) {
+                Ok(piece_move) => piece_move,
+                Err(message) => {
+                    let context = format!("{} {}", move_number(&board), pgn_move.san);
+
+                    return (replayed, Some(error(pgn_move.line, &context, message)));
+                }
+            };
+
+            board.make_move(piece_move);
+            replayed.moves.push(piece_move);
+            replayed.positions.push(board.clone());
+        }
+
+        (replayed, None)
+    }
+
+    pub fn replay(&self) -> Result<ReplayedGame, PgnError> {
+        match self.replay_partial() {
+            (replayed, None) => Ok(replayed),
+            (_, Some(err)) => Err(err),
+        }
+    }
+}
+
+// exported movetext lines are kept under this length
+pub const PGN_LINE_LENGTH: usize = 80;
+
+impl Default for PgnHeader {
+    fn default() -> PgnHeader {
+        PgnHeader {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: today(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+            result: "*".to_string(),
+        }
+    }
+}
+
+// utc date of today in the pgn format, days since 1970 to a civil date
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// score in pawns like +0.35, or mate in moves like +M3, with the search depth. positive
+// is good for white whoever played the move, like pgn readers expect
+fn eval_comment(eval: MoveEval, color: Color) -> String {
+    let score = match color {
+        Color::White => eval.score,
+        Color::Black => -eval.score,
+    };
+    let mate_plies = CHECKMATE - score.abs();
+    let sign = if score < 0 { '-' } else { '+' };
+
+    match mate_plies <= MAX_PLY as i32 {
+        true => format!("{}M{}/{}", sign, (mate_plies + 1) / 2, eval.depth),
+        false => format!("{}{:.2}/{}", sign, score.abs() as f64 / 100.0, eval.depth),
+    }
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// pgn of a game played from start, evals are comments after their moves when given,
+// written from white's point of view
+pub fn write_pgn(
+    header: &PgnHeader,
+    start: &BoardState,
+    moves: &[EncodedMove],
+    evals: Option<&[MoveEval]>,
+) -> Result<String, &'static str> {
+    if evals.is_some_and(|evals| evals.len() != moves.len()) {
+        return Err("Every move needs an eval!");
+    }
+
+    let mut board = start.clone();
+    let fen = board.to_fen();
+    let mut text = String::new();
+
+    for (name, value) in [
+        ("Event", &header.event),
+        ("Site", &header.site),
+        ("Date", &header.date),
+        ("Round", &header.round),
+        ("White", &header.white),
+        ("Black", &header.black),
+        ("Result", &header.result),
+    ] {
+        text += &format!("[{} \"{}\"]\n", name, escape_tag(value));
+    }
+
+    if fen != DEFAULT_FEN_STRING {
+        text += &format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", fen);
+    }
+
+    text.push('\n');
+
+    let mut tokens = vec![];
+    // black moves need their number at the start and after a comment
+    let mut needs_number = true;
+
+    for (index, &piece_move) in moves.iter().enumerate() {
+        if !board.generate_legal_moves().contains(&piece_move) {
+            return Err("Illegal move in the exported game!");
+        }
+
+        if matches!(board.to_move, Color::White) || needs_number {
+            tokens.push(move_number(&board));
+        }
+
+        tokens.push(move_to_san(&mut board, piece_move));
+        needs_number = false;
+
+        if let Some(evals) = evals {
+            tokens.push(format!("{{{}}}", eval_comment(evals[index], board.to_move)));
+            needs_number = true;
+        }
+
+        board.make_move(piece_move);
+    }
+
+    tokens.push(header.result.clone());
+
+    let mut line = String::new();
+
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_LENGTH {
+            text += &line;
+            text.push('\n');
+            line.clear();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+
+        line += &token;
+    }
+
+    text += &line;
+    text.push('\n');
+
+    Ok(text)
+}
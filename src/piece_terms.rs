@@ -0,0 +1,139 @@
+use crate::{
+    board::{Bitboard, BoardState, Color, Piece},
+    constants::{BOARD_SQUARES, PAWN_ATTACKS},
+    eval_params::EvalParams,
+    mobility::piece_attacks,
+    pawn_structure::{adjacent_files, relative_rank, rows_ahead, FILES, RANKS},
+    piece_parsing::parse_bitboards,
+    utils::opposite_color,
+};
+
+// a8 is a light square
+pub const LIGHT_SQUARES: Bitboard = 0xAA55AA55AA55AA55;
+
+// (middlegame, endgame) values
+pub const ROOK_OPEN_FILE_BONUS: (i32, i32) = (25, 10);
+pub const ROOK_SEMI_OPEN_FILE_BONUS: (i32, i32) = (12, 5);
+// only when the enemy king is stuck on its back rank or there are pawns to eat
+pub const ROOK_SEVENTH_RANK_BONUS: (i32, i32) = (20, 30);
+pub const CONNECTED_ROOKS_BONUS: (i32, i32) = (10, 5);
+// for every friendly pawn on the squares of the bishop's color
+pub const BAD_BISHOP_PENALTY: (i32, i32) = (-3, -6);
+// on the 4th to 6th rank, defended by a pawn and out of reach of enemy pawns
+pub const KNIGHT_OUTPOST_BONUS: (i32, i32) = (25, 15);
+pub const BISHOP_OUTPOST_BONUS: (i32, i32) = (15, 8);
+// rook shut in the corner by its own king
+pub const TRAPPED_ROOK_PENALTY: (i32, i32) = (-40, -10);
+// bishop that took the a7 (h7) pawn and got shut in by b6 (g6)
+pub const TRAPPED_BISHOP_PENALTY: (i32, i32) = (-100, -100);
+
+// (bishop square, pawn square) pairs of a trapped bishop by color
+const TRAPPED_BISHOP_SQUARES: [[(usize, usize); 2]; 2] =
+    [[(8, 17), (15, 22)], [(48, 41), (55, 46)]];
+
+fn is_outpost(board: &BoardState, color: Color, square: usize) -> bool {
+    let bb_pawns = board.get_piece_bb(color, Piece::Pawn);
+    let bb_enemy_pawns = board.get_piece_bb(opposite_color(color), Piece::Pawn);
+
+    let rank = relative_rank(color, square);
+    // friendly pawns that attack this square defend it
+    let defended = PAWN_ATTACKS[opposite_color(color) as usize][square] & bb_pawns != 0;
+    let bb_attack_span = adjacent_files(square % 8) & rows_ahead(color, square / 8);
+
+    (3..=5).contains(&rank) && defended && bb_attack_span & bb_enemy_pawns == 0
+}
+
+// rooks, bishops and knights beyond piece-square tables of one color, (middlegame, endgame)
+pub fn piece_terms_score(board: &BoardState, color: Color, params: &EvalParams) -> (i32, i32) {
+    let enemy_color = opposite_color(color);
+    let bb_pawns = board.get_piece_bb(color, Piece::Pawn);
+    let bb_enemy_pawns = board.get_piece_bb(enemy_color, Piece::Pawn);
+    let bb_rooks = board.get_piece_bb(color, Piece::Rook);
+    let bb_king = board.get_piece_bb(color, Piece::King);
+
+    let mut middlegame_score = 0;
+    let mut endgame_score = 0;
+
+    let mut add = |(middlegame, endgame): (i32, i32)| {
+        middlegame_score += middlegame;
+        endgame_score += endgame;
+    };
+
+    for rook in parse_bitboards(color, bb_rooks).iter() {
+        let square = rook.1 as usize;
+        let bb_file = FILES[square % 8];
+        let bb_attacks = piece_attacks(Piece::Rook, square, board.bb_fullboard);
+
+        if bb_file & (bb_pawns | bb_enemy_pawns) == 0 {
+            add(params.rook_open_file);
+        } else if bb_file & bb_pawns == 0 {
+            add(params.rook_semi_open_file);
+        }
+
+        if relative_rank(color, square) == 6 {
+            let enemy_king_square = board
+                .get_piece_bb(enemy_color, Piece::King)
+                .trailing_zeros();
+            let enemy_king_on_back_rank =
+                enemy_king_square < 64 && relative_rank(color, enemy_king_square as usize) == 7;
+            let pawns_on_rank = bb_enemy_pawns & RANKS[square / 8] != 0;
+
+            if enemy_king_on_back_rank || pawns_on_rank {
+                add(params.rook_seventh_rank);
+            }
+        }
+
+        // rooks defending each other, counted once from the rook on the lower square
+        let bb_higher_squares = !(BOARD_SQUARES[square] - 1) & !BOARD_SQUARES[square];
+
+        if bb_attacks & bb_rooks & bb_higher_squares != 0 {
+            add(params.connected_rooks);
+        }
+
+        // few squares on the back rank and the king between it and the center
+        if bb_king != 0 && relative_rank(color, square) == 0 {
+            let king_square = bb_king.trailing_zeros() as usize;
+            let mobility = (bb_attacks & !board.get_color_bb(color)).count_ones();
+            let shut_in = match king_square % 8 < 4 {
+                true => square % 8 < king_square % 8,
+                false => square % 8 > king_square % 8,
+            };
+
+            if mobility <= 3 && shut_in && king_square / 8 == square / 8 {
+                add(params.trapped_rook);
+            }
+        }
+    }
+
+    for bishop in parse_bitboards(color, board.get_piece_bb(color, Piece::Bishop)).iter() {
+        let square = bishop.1 as usize;
+        let bb_same_color_squares = match LIGHT_SQUARES & BOARD_SQUARES[square] != 0 {
+            true => LIGHT_SQUARES,
+            false => !LIGHT_SQUARES,
+        };
+        let bad_pawns = (bb_pawns & bb_same_color_squares).count_ones() as i32;
+
+        add((
+            params.bad_bishop.0 * bad_pawns,
+            params.bad_bishop.1 * bad_pawns,
+        ));
+
+        if is_outpost(board, color, square) {
+            add(params.bishop_outpost);
+        }
+
+        for (bishop_square, pawn_square) in TRAPPED_BISHOP_SQUARES[color as usize] {
+            if square == bishop_square && bb_enemy_pawns & BOARD_SQUARES[pawn_square] != 0 {
+                add(params.trapped_bishop);
+            }
+        }
+    }
+
+    for knight in parse_bitboards(color, board.get_piece_bb(color, Piece::Knight)).iter() {
+        if is_outpost(board, color, knight.1 as usize) {
+            add(params.knight_outpost);
+        }
+    }
+
+    (middlegame_score, endgame_score)
+}
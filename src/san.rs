@@ -0,0 +1,233 @@
+use crate::{
+    board::{BoardState, EncodedMove, Piece},
+    utils::square_to_string,
+};
+
+// a8 is 0, so the rank counts down from the top
+fn parse_square(file: char, rank: char) -> Option<u32> {
+    let file = "abcdefgh".find(file)? as u32;
+    let rank = rank.to_digit(10).filter(|rank| (1..=8).contains(rank))?;
+
+    Some((8 - rank) * 8 + file)
+}
+
+fn parse_piece(letter: char) -> Option<Piece> {
+    match letter {
+        'N' => Some(Piece::Knight),
+        'B' => Some(Piece::Bishop),
+        'R' => Some(Piece::Rook),
+        'Q' => Some(Piece::Queen),
+        'K' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+// castling is the king going two files to the side of the rook
+fn is_castling(piece_move: EncodedMove) -> bool {
+    let from = piece_move & 63;
+    let to = (piece_move >> 6) & 63;
+
+    (piece_move >> 12) & 7 == Piece::King as u32 && from.abs_diff(to) == 2
+}
+
+fn is_promotion(piece_move: EncodedMove) -> bool {
+    (piece_move >> 19) & 1 == 1
+}
+
+// piece a pawn promotes to, only meaningful with the promotion flag
+fn promotion_piece(piece_move: EncodedMove) -> u32 {
+    (piece_move >> 20) & 7
+}
+
+fn parse_castling(board: &mut BoardState, san: &str) -> Result<EncodedMove, &'static str> {
+    // 0 is kingside and 1 queenside like the castling rights
+    let side = match san {
+        "O-O" | "0-0" => 0,
+        "O-O-O" | "0-0-0" => 1,
+        _ => return Err("Incorrect move notation!"),
+    };
+
+    let castling = board
+        .generate_legal_moves()
+        .into_iter()
+        .find(|&piece_move| {
+            is_castling(piece_move) && ((piece_move >> 6) & 63 < piece_move & 63) == (side == 1)
+        });
+
+    castling.ok_or("Illegal move!")
+}
+
+// standard algebraic notation like Nbd7, exd5, e8=Q or O-O, resolved against the legal
+// moves. check marks and annotations like + # ! ? are ignored
+pub fn parse_san(board: &mut BoardState, san: &str) -> Result<EncodedMove, &'static str> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+    if san.starts_with("O-O") || san.starts_with("0-0") {
+        return parse_castling(board, san);
+    }
+
+    let (san, promotion) = match san.split_once('=') {
+        Some((san, letter @ ("N" | "B" | "R" | "Q"))) => {
+            (san, letter.chars().next().and_then(parse_piece))
+        }
+        Some(_) => return Err("Incorrect move notation!"),
+        None => (san, None),
+    };
+
+    let (piece, rest) = match san.chars().next().and_then(parse_piece) {
+        Some(piece) => (piece, &san[1..]),
+        None => (Piece::Pawn, san),
+    };
+
+    if promotion.is_some() && !matches!(piece, Piece::Pawn) {
+        return Err("Incorrect move notation!");
+    }
+
+    let capture = rest.contains('x');
+    let chars: Vec<char> = rest.chars().filter(|&c| c != 'x').collect();
+
+    if chars.len() < 2 || chars.len() > 4 {
+        return Err("Incorrect move notation!");
+    }
+
+    let to = parse_square(chars[chars.len() - 2], chars[chars.len() - 1])
+        .ok_or("Incorrect move notation!")?;
+
+    // file and rank of the moving piece when they are given
+    let mut from_file = None;
+    let mut from_rank = None;
+
+    for &c in &chars[..chars.len() - 2] {
+        match c {
+            'a'..='h' => from_file = Some(c as u32 - 'a' as u32),
+            '1'..='8' => from_rank = Some(c as u32 - '1' as u32),
+            _ => return Err("Incorrect move notation!"),
+        }
+    }
+
+    // pawns only leave their file when capturing
+    if matches!(piece, Piece::Pawn) && !capture {
+        from_file = Some(to % 8);
+    }
+
+    let mut candidates: Vec<EncodedMove> = board
+        .generate_legal_moves()
+        .into_iter()
+        .filter(|&piece_move| {
+            let from = piece_move & 63;
+
+            (piece_move >> 12) & 7 == piece as u32
+                && (piece_move >> 6) & 63 == to
+                && from_file.is_none_or(|file| from % 8 == file)
+                && from_rank.is_none_or(|rank| 7 - from / 8 == rank)
+                && !is_castling(piece_move)
+        })
+        .collect();
+
+    // a pawn reaching the last rank has one move for every piece it can become
+    if promotion.is_none()
+        && candidates
+            .iter()
+            .any(|&piece_move| is_promotion(piece_move))
+    {
+        return Err("Missing promotion piece!");
+    }
+
+    candidates.retain(|&piece_move| match promotion {
+        Some(promotion) => {
+            is_promotion(piece_move) && promotion_piece(piece_move) == promotion as u32
+        }
+        None => true,
+    });
+
+    match candidates[..] {
+        [piece_move] => Ok(piece_move),
+        [] => Err("Illegal move!"),
+        _ => Err("Ambiguous move!"),
+    }
+}
+
+const PIECE_LETTERS: [&str; 6] = ["", "N", "B", "R", "Q", "K"];
+
+// standard algebraic notation of a legal move, with the file or rank of the moving piece
+// only when another piece of the same kind can go to the same square
+pub fn move_to_san(board: &mut BoardState, piece_move: EncodedMove) -> String {
+    let mut san = move_to_san_without_check(board, piece_move);
+
+    board.make_move(piece_move);
+
+    let color = board.to_move;
+
+    if board.is_in_check(&color) {
+        san.push(match board.generate_legal_moves().is_empty() {
+            true => '#',
+            false => '+',
+        });
+    }
+
+    let _ = board.undo_move();
+
+    san
+}
+
+fn move_to_san_without_check(board: &mut BoardState, piece_move: EncodedMove) -> String {
+    let from = piece_move & 63;
+    let to = (piece_move >> 6) & 63;
+    let piece = (piece_move >> 12) & 7;
+    let capture = (piece_move >> 16) & 7 != Piece::None as u32;
+    let file = |square: u32| (b'a' + (square % 8) as u8) as char;
+    let rank = |square: u32| (b'1' + (7 - square / 8) as u8) as char;
+
+    if is_castling(piece_move) {
+        return match to % 8 > from % 8 {
+            true => "O-O".to_string(),
+            false => "O-O-O".to_string(),
+        };
+    }
+
+    let mut san = String::from(PIECE_LETTERS[piece as usize]);
+
+    if piece == Piece::Pawn as u32 {
+        // en passant captures land on an empty square
+        if from % 8 != to % 8 {
+            san.push(file(from));
+            san.push('x');
+        }
+    } else {
+        let others: Vec<u32> = board
+            .generate_legal_moves()
+            .into_iter()
+            .filter(|&other| {
+                other != piece_move
+                    && (other >> 12) & 7 == piece
+                    && (other >> 6) & 63 == to
+                    && other & 63 != from
+            })
+            .map(|other| other & 63)
+            .collect();
+
+        if !others.is_empty() {
+            if others.iter().all(|&other| other % 8 != from % 8) {
+                san.push(file(from));
+            } else if others.iter().all(|&other| other / 8 != from / 8) {
+                san.push(rank(from));
+            } else {
+                san.push(file(from));
+                san.push(rank(from));
+            }
+        }
+
+        if capture {
+            san.push('x');
+        }
+    }
+
+    san += &square_to_string(to);
+
+    if is_promotion(piece_move) {
+        san.push('=');
+        san += PIECE_LETTERS[promotion_piece(piece_move) as usize];
+    }
+
+    san
+}
@@ -1,49 +1,602 @@
 use crate::{
-    board::BoardState,
+    board::{BoardState, EncodedMove, Piece},
+    endgame::kpk_score,
     eval::{evaluate, CHECKMATE, DRAW},
+    eval_params::EvalParams,
+    pawn_hash::{PawnHashTable, DEFAULT_PAWN_HASH_SIZE_MB},
+    see::{see, see_ge},
+    tt::{score_from_tt, score_to_tt, Bound, TTEntry, TranspositionTable},
 };
-use std::process::exit;
+use std::{
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const MAX_PLY: usize = 64;
+// extensions can at most double the depth, so lines still fit into MAX_PLY
+pub const MAX_DEPTH: u8 = (MAX_PLY / 2) as u8;
+pub const INFINITY: i32 = 100_000_000;
 
-pub fn negamax(board: &mut BoardState, depth: u8) -> i32 {
-    let mut max: i32 = -100_000_000;
+// singular extensions are only tried on nodes at least this deep
+pub const SINGULAR_MIN_DEPTH: u8 = 4;
+// how much worse (per depth) every other move has to be for the tt move to be singular
+pub const SINGULAR_MARGIN: i32 = 25;
 
-    if depth == 0 {
-        let score = evaluate(board);
+// no limits at all means searching until MAX_DEPTH or the stop flag. infinite searches
+// only return once the stop flag is set, even after reaching MAX_DEPTH
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    // milliseconds
+    pub movetime: Option<u64>,
+    // mate in N moves
+    pub mate: Option<u8>,
+    pub infinite: bool,
+}
 
-        return score;
+impl SearchLimits {
+    pub fn depth(depth: u8) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn max_depth(&self) -> u8 {
+        match (self.depth, self.mate) {
+            (Some(depth), _) => depth.min(MAX_DEPTH),
+            // mate in N takes 2N - 1 plies
+            (None, Some(mate)) => (mate.max(1) * 2 - 1).min(MAX_DEPTH),
+            _ => MAX_DEPTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PvLine {
+    pub score: i32,
+    pub moves: Vec<EncodedMove>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub score: i32,
+    pub best_move: Option<EncodedMove>,
+    pub depth: u8,
+    pub nodes: u64,
+    // best lines first, as many as multipv asked for (and there are legal moves)
+    pub pv_lines: Vec<PvLine>,
+}
+
+pub struct Search {
+    pub tt: Arc<TranspositionTable>,
+    pub pawn_table: PawnHashTable,
+    pub params: EvalParams,
+    pub nodes: u64,
+    pub best_move: Option<EncodedMove>,
+    pub best_score: i32,
+    // 0 is the main thread, helper threads order quiet moves a bit differently
+    pub thread_id: usize,
+    pub limits: SearchLimits,
+    // number of root lines to search, each one without the best moves of the previous ones
+    pub multipv: usize,
+    // set from outside (or by hitting a limit) to abort the search
+    pub stop: Arc<AtomicBool>,
+    // nodes of every thread searching together, the node limit is checked against it
+    pub shared_nodes: Arc<AtomicU64>,
+    stopped: bool,
+    // nodes already added to shared_nodes
+    flushed_nodes: u64,
+    start_time: Instant,
+    root_excluded_moves: Vec<EncodedMove>,
+    // triangular pv table, pv_table[ply] holds the line from that ply on
+    pv_table: [[EncodedMove; MAX_PLY + 1]; MAX_PLY + 1],
+    pv_length: [usize; MAX_PLY + 1],
+}
+
+impl Search {
+    pub fn with_tt(tt: Arc<TranspositionTable>, thread_id: usize, stop: Arc<AtomicBool>) -> Search {
+        Search {
+            tt,
+            pawn_table: PawnHashTable::new(DEFAULT_PAWN_HASH_SIZE_MB),
+            params: EvalParams::default(),
+            nodes: 0,
+            best_move: None,
+            best_score: 0,
+            thread_id,
+            limits: SearchLimits::default(),
+            multipv: 1,
+            stop,
+            shared_nodes: Arc::new(AtomicU64::new(0)),
+            stopped: false,
+            flushed_nodes: 0,
+            start_time: Instant::now(),
+            root_excluded_moves: vec![],
+            pv_table: [[0; MAX_PLY + 1]; MAX_PLY + 1],
+            pv_length: [0; MAX_PLY + 1],
+        }
     }
 
-    let moves = board.generate_moves_by_color(&board.to_move);
+    // iterative deepening, every line may be extended at most `depth` plies.
+    // when stopped the best move of the last iteration is kept, or a better one
+    // from the unfinished iteration, pv lines always come from the last finished one
+    pub fn search(&mut self, board: &mut BoardState, limits: SearchLimits) -> SearchResult {
+        let max_depth = limits.max_depth();
+        let mut completed_depth = 0;
+        let mut pv_lines = vec![];
 
-    if moves.is_empty() {
-        if board.is_in_check(&board.to_move) {
-            return CHECKMATE;
+        self.nodes = 0;
+        self.best_move = None;
+        self.best_score = 0;
+        self.limits = limits;
+        self.stopped = false;
+        self.flushed_nodes = 0;
+        self.start_time = Instant::now();
+
+        // odd helper threads skip the first iteration so threads don't search in lockstep
+        let start_depth = (1 + (self.thread_id % 2) as u8).min(max_depth);
+
+        for current_depth in start_depth..=max_depth {
+            let mut iteration_lines: Vec<PvLine> = vec![];
+
+            self.root_excluded_moves.clear();
+
+            for _ in 0..self.multipv.max(1) {
+                let score = self.negamax(
+                    board,
+                    current_depth,
+                    0,
+                    -INFINITY,
+                    INFINITY,
+                    current_depth,
+                    None,
+                );
+
+                // every legal root move already has its own line
+                if self.stopped || self.pv_length[0] == 0 {
+                    break;
+                }
+
+                let moves = self.pv_table[0][..self.pv_length[0]].to_vec();
+
+                self.root_excluded_moves.push(moves[0]);
+                iteration_lines.push(PvLine { score, moves });
+            }
+
+            if self.stopped {
+                break;
+            }
+
+            iteration_lines.sort_by_key(|line| -line.score);
+
+            pv_lines = iteration_lines;
+            completed_depth = current_depth;
+
+            if let Some(mate) = limits.mate {
+                if self.best_score >= CHECKMATE - (mate as i32 * 2 - 1) {
+                    break;
+                }
+            }
+        }
+
+        self.root_excluded_moves.clear();
+
+        // an infinite search that ran out of depth holds its result until it's stopped,
+        // the best move is only reported after stop
+        while limits.infinite && !self.stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(1));
         }
 
-        return DRAW;
+        SearchResult {
+            score: self.best_score,
+            best_move: self.best_move,
+            depth: completed_depth,
+            nodes: self.nodes,
+            pv_lines,
+        }
     }
 
-    for piece_move in moves.iter() {
-        let (_, _, _, color, _, _) = board.decode_move(*piece_move).unwrap_or_else(|err| {
-            println!("{}", err);
-            exit(1);
-        });
+    // time is only looked at every 1024 nodes, and the nodes of this thread are added to
+    // the shared count as often, so other threads see them at most that late
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+
+        if self.nodes - self.flushed_nodes >= 1024 {
+            self.shared_nodes
+                .fetch_add(self.nodes - self.flushed_nodes, Ordering::Relaxed);
+            self.flushed_nodes = self.nodes;
+        }
+
+        let total_nodes =
+            self.shared_nodes.load(Ordering::Relaxed) + self.nodes - self.flushed_nodes;
+        let out_of_nodes = matches!(self.limits.nodes, Some(nodes) if total_nodes >= nodes);
+        let out_of_time = self.nodes.is_multiple_of(1024)
+            && matches!(self.limits.movetime, Some(movetime)
+                if self.start_time.elapsed().as_millis() >= movetime as u128);
+
+        if out_of_nodes || out_of_time {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+
+        self.stopped = self.stop.load(Ordering::Relaxed);
+
+        self.stopped
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn negamax(
+        &mut self,
+        board: &mut BoardState,
+        depth: u8,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        extensions_left: u8,
+        excluded_move: Option<EncodedMove>,
+    ) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+
+        self.nodes += 1;
+        self.pv_length[ply] = 0;
+
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let tt_entry = self.tt.probe(board.key);
+
+        // the node is searched without its best move when looking for singular moves,
+        // so the stored result can't be used there
+        if let (Some(entry), None) = (tt_entry, excluded_move) {
+            let score = score_from_tt(entry.score, ply);
 
-        board.make_move(*piece_move);
+            if ply > 0 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        // king and pawn against king is known exactly, no need to search it
+        if ply > 0 && board.bb_fullboard.count_ones() == 3 {
+            if let Some(score) = kpk_score(board) {
+                return score;
+            }
+        }
+
+        let mut moves = board.generate_moves_by_color(&board.to_move);
+
+        order_moves(
+            board,
+            &mut moves,
+            tt_entry.and_then(|entry| entry.best_move),
+            self.thread_id,
+        );
+
+        let singular_move =
+            self.singular_move(board, tt_entry, depth, ply, extensions_left, excluded_move);
+
+        // singular search at the same ply leaves its own line behind
+        self.pv_length[ply] = 0;
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+
+        for piece_move in moves.into_iter() {
+            if Some(piece_move) == excluded_move
+                || (ply == 0 && self.root_excluded_moves.contains(&piece_move))
+            {
+                continue;
+            }
+
+            let (_, _, _, color, _, _) = board.decode_move(piece_move).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            let recapture = is_recapture(board, piece_move);
+
+            board.make_move(piece_move);
+
+            if board.is_in_check(&color) {
+                let _ = board.undo_move();
+                continue;
+            }
+
+            legal_moves += 1;
+
+            let gives_check = board.is_in_check(&board.to_move);
+            let extension = extension(
+                gives_check,
+                recapture,
+                singular_move == Some(piece_move),
+                extensions_left,
+            );
+
+            let score = -self.negamax(
+                board,
+                depth - 1 + extension,
+                ply + 1,
+                -beta,
+                -alpha,
+                extensions_left - extension,
+                None,
+            );
 
-        if board.is_in_check(&color) {
             let _ = board.undo_move();
-            continue;
+
+            // the score of an aborted subtree means nothing
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(piece_move);
+
+                if ply == 0 && self.root_excluded_moves.is_empty() {
+                    self.best_move = best_move;
+                    self.best_score = best_score;
+                }
+            }
+
+            if score > alpha {
+                alpha = score;
+
+                self.update_pv(ply, piece_move);
+            }
+
+            if alpha >= beta {
+                break;
+            }
         }
 
-        let score = negamax(board, depth - 1);
+        if legal_moves == 0 {
+            // the excluded move was the only legal one, so it's singular for sure
+            if excluded_move.is_some() {
+                return alpha;
+            }
 
-        if -score > max {
-            max = -score;
+            // no root moves left for another multipv line
+            if ply == 0 && !self.root_excluded_moves.is_empty() {
+                return -INFINITY;
+            }
+
+            if board.is_in_check(&board.to_move) {
+                return -CHECKMATE + ply as i32;
+            }
+
+            return DRAW;
         }
 
-        let _ = board.undo_move();
+        if excluded_move.is_none() && (ply > 0 || self.root_excluded_moves.is_empty()) {
+            let bound = if best_score >= beta {
+                Bound::Lower
+            } else if best_score > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+
+            self.tt.store(
+                board.key,
+                best_move,
+                score_to_tt(best_score, ply),
+                depth,
+                bound,
+            );
+        }
+
+        best_score
+    }
+
+    fn update_pv(&mut self, ply: usize, piece_move: EncodedMove) {
+        let child_length = if ply < MAX_PLY {
+            self.pv_length[ply + 1]
+        } else {
+            0
+        };
+
+        self.pv_table[ply][0] = piece_move;
+
+        for index in 0..child_length.min(MAX_PLY - ply) {
+            self.pv_table[ply][index + 1] = self.pv_table[ply + 1][index];
+        }
+
+        self.pv_length[ply] = child_length.min(MAX_PLY - ply) + 1;
     }
 
-    max
+    // only captures that don't lose material are searched until the position is quiet
+    pub fn quiescence(
+        &mut self,
+        board: &mut BoardState,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+
+        self.nodes += 1;
+        self.pv_length[ply] = 0;
+
+        let stand_pat = evaluate(board, &mut self.pawn_table, &self.params);
+
+        if ply >= MAX_PLY || stand_pat >= beta {
+            return stand_pat;
+        }
+
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let mut captures: Vec<EncodedMove> = board
+            .generate_moves_by_color(&board.to_move)
+            .into_iter()
+            .filter(|&piece_move| is_capture(board, piece_move) && see_ge(board, piece_move, 0))
+            .collect();
+
+        order_moves(board, &mut captures, None, self.thread_id);
+
+        for piece_move in captures.into_iter() {
+            let (_, _, _, color, _, _) = board.decode_move(piece_move).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            board.make_move(piece_move);
+
+            if board.is_in_check(&color) {
+                let _ = board.undo_move();
+                continue;
+            }
+
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+
+            let _ = board.undo_move();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        alpha
+    }
+
+    // tt move is singular if a reduced search without it fails low against tt score - margin
+    fn singular_move(
+        &mut self,
+        board: &mut BoardState,
+        tt_entry: Option<TTEntry>,
+        depth: u8,
+        ply: usize,
+        extensions_left: u8,
+        excluded_move: Option<EncodedMove>,
+    ) -> Option<EncodedMove> {
+        if ply == 0 || depth < SINGULAR_MIN_DEPTH || extensions_left == 0 || excluded_move.is_some()
+        {
+            return None;
+        }
+
+        let entry = tt_entry?;
+        let tt_move = entry.best_move?;
+        let tt_score = score_from_tt(entry.score, ply);
+
+        if entry.bound == Bound::Upper
+            || entry.depth + 3 < depth
+            || tt_score.abs() >= CHECKMATE - MAX_PLY as i32
+        {
+            return None;
+        }
+
+        let singular_beta = tt_score - SINGULAR_MARGIN * depth as i32;
+
+        let score = self.negamax(
+            board,
+            (depth - 1) / 2,
+            ply,
+            singular_beta - 1,
+            singular_beta,
+            extensions_left,
+            Some(tt_move),
+        );
+
+        if score < singular_beta {
+            Some(tt_move)
+        } else {
+            None
+        }
+    }
+}
+
+// check, recapture and singular moves are extended by one ply while the line has budget left
+pub fn extension(gives_check: bool, recapture: bool, singular: bool, extensions_left: u8) -> u8 {
+    if extensions_left > 0 && (gives_check || recapture || singular) {
+        1
+    } else {
+        0
+    }
+}
+
+pub fn is_capture(board: &BoardState, piece_move: EncodedMove) -> bool {
+    let (_, _, _, _, captured_piece, _) = board.decode_move(piece_move).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+
+    !matches!(captured_piece, Piece::None)
+}
+
+// tt move first, then winning and equal captures by see, quiet moves and losing captures last
+pub fn order_moves(
+    board: &BoardState,
+    moves: &mut [EncodedMove],
+    tt_move: Option<EncodedMove>,
+    thread_id: usize,
+) {
+    moves.sort_by_cached_key(|&piece_move| {
+        if Some(piece_move) == tt_move {
+            i32::MIN
+        } else if is_capture(board, piece_move) {
+            let see_score = see(board, piece_move);
+
+            if see_score >= 0 {
+                -INFINITY - see_score
+            } else {
+                INFINITY - see_score
+            }
+        } else if thread_id == 0 {
+            0
+        } else {
+            // helper threads shuffle quiet moves, same order every time for the same thread
+            ((piece_move as u64 ^ thread_id as u64).wrapping_mul(0x9E3779B97F4A7C15) >> 54) as i32
+        }
+    });
+}
+
+// move captures back on the square where the previous move captured
+pub fn is_recapture(board: &BoardState, piece_move: EncodedMove) -> bool {
+    let last_move = match board.move_history.last() {
+        Some(last_move) => *last_move,
+        None => return false,
+    };
+
+    let (_, last_end_bb, _, _, last_captured_piece, _) =
+        board.decode_move(last_move).unwrap_or_else(|err| {
+            println!("{}", err);
+            exit(1);
+        });
+    let (_, end_bb, _, _, captured_piece, _) =
+        board.decode_move(piece_move).unwrap_or_else(|err| {
+            println!("{}", err);
+            exit(1);
+        });
+
+    !matches!(last_captured_piece, Piece::None)
+        && !matches!(captured_piece, Piece::None)
+        && last_end_bb == end_bb
 }
@@ -0,0 +1,119 @@
+use crate::{
+    board::{Bitboard, BoardState, Color, EncodedMove, Piece},
+    constants::{BISHOP_MAGICS, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, ROOK_MAGICS},
+    eval::{BISHOP, KING, KNIGHT, PAWN, QUEEN, ROOK},
+    magic::{get_bishop_move, get_rook_move},
+    utils::{number_to_piece, opposite_color},
+};
+use std::process::exit;
+
+// indexed by piece, Piece::None is worth nothing
+pub const SEE_VALUES: [i32; 7] = [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING, 0];
+
+// pieces of both colors attacking the square with the given occupancy
+pub fn attackers_to(board: &BoardState, square: usize, bb_occupancy: Bitboard) -> Bitboard {
+    let bb_bishops_queens = board.get_piece_bb(Color::White, Piece::Bishop)
+        | board.get_piece_bb(Color::Black, Piece::Bishop)
+        | board.get_piece_bb(Color::White, Piece::Queen)
+        | board.get_piece_bb(Color::Black, Piece::Queen);
+    let bb_rooks_queens = board.get_piece_bb(Color::White, Piece::Rook)
+        | board.get_piece_bb(Color::Black, Piece::Rook)
+        | board.get_piece_bb(Color::White, Piece::Queen)
+        | board.get_piece_bb(Color::Black, Piece::Queen);
+
+    // pawn attacks are looked up from the square with the opposite color
+    (PAWN_ATTACKS[Color::Black as usize][square] & board.get_piece_bb(Color::White, Piece::Pawn))
+        | (PAWN_ATTACKS[Color::White as usize][square]
+            & board.get_piece_bb(Color::Black, Piece::Pawn))
+        | (KNIGHT_ATTACKS[square]
+            & (board.get_piece_bb(Color::White, Piece::Knight)
+                | board.get_piece_bb(Color::Black, Piece::Knight)))
+        | (KING_ATTACKS[square]
+            & (board.get_piece_bb(Color::White, Piece::King)
+                | board.get_piece_bb(Color::Black, Piece::King)))
+        | (get_bishop_move(BISHOP_MAGICS[square], bb_occupancy) & bb_bishops_queens)
+        | (get_rook_move(ROOK_MAGICS[square], bb_occupancy) & bb_rooks_queens)
+}
+
+fn least_valuable_attacker(
+    board: &BoardState,
+    bb_attackers: Bitboard,
+    color: Color,
+) -> Option<(Bitboard, Piece)> {
+    for index in 0..6 {
+        let bb_pieces = bb_attackers & board.bb_pieces[color as usize][index];
+
+        if bb_pieces != 0 {
+            let piece = number_to_piece(index as u32).unwrap_or_else(|err| {
+                println!("{}", err);
+                exit(1);
+            });
+
+            return Some((bb_pieces & bb_pieces.wrapping_neg(), piece));
+        }
+    }
+
+    None
+}
+
+// static exchange evaluation, material balance of the capture sequence on the move square
+// when both sides always recapture with their least valuable attacker
+pub fn see(board: &BoardState, piece_move: EncodedMove) -> i32 {
+    let (start_bb, end_bb, piece, color, captured_piece, _) =
+        board.decode_move(piece_move).unwrap_or_else(|err| {
+            println!("{}", err);
+            exit(1);
+        });
+
+    let square = end_bb.trailing_zeros() as usize;
+
+    let mut gain = [0; 32];
+    let mut depth = 0;
+
+    let mut bb_occupancy = board.bb_fullboard;
+    let mut bb_attackers = attackers_to(board, square, bb_occupancy);
+    let mut bb_from = start_bb;
+    let mut attacker = piece;
+    let mut side = color;
+
+    gain[0] = SEE_VALUES[captured_piece as usize];
+
+    loop {
+        depth += 1;
+
+        // score if the attacker gets captured back
+        gain[depth] = SEE_VALUES[attacker as usize] - gain[depth - 1];
+
+        // neither side can gain anything by continuing
+        if (-gain[depth - 1]).max(gain[depth]) < 0 || depth == gain.len() - 1 {
+            break;
+        }
+
+        bb_attackers &= !bb_from;
+        bb_occupancy &= !bb_from;
+
+        // sliders behind the piece that just captured can join now (x-rays)
+        bb_attackers |= attackers_to(board, square, bb_occupancy) & bb_occupancy;
+
+        side = opposite_color(side);
+
+        match least_valuable_attacker(board, bb_attackers, side) {
+            Some((bb_next, next_attacker)) => {
+                bb_from = bb_next;
+                attacker = next_attacker;
+            }
+            None => break,
+        }
+    }
+
+    while depth > 1 {
+        depth -= 1;
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+    }
+
+    gain[0]
+}
+
+pub fn see_ge(board: &BoardState, piece_move: EncodedMove, threshold: i32) -> bool {
+    see(board, piece_move) >= threshold
+}
@@ -0,0 +1,83 @@
+use crate::{
+    board::{BoardState, Color, EncodedMove},
+    options::EngineOptions,
+    pgn::MoveEval,
+    search::SearchLimits,
+    smp::lazy_smp,
+    tt::TranspositionTable,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+pub const DEFAULT_SELFPLAY_PLIES: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct SelfPlayGame {
+    pub start: BoardState,
+    pub moves: Vec<EncodedMove>,
+    pub evals: Vec<MoveEval>,
+    pub result: String,
+}
+
+// result of a position without legal moves, mate or stalemate
+fn final_result(board: &mut BoardState) -> &'static str {
+    let color = board.to_move;
+
+    match (board.is_in_check(&color), color) {
+        (true, Color::White) => "0-1",
+        (true, Color::Black) => "1-0",
+        (false, _) => "1/2-1/2",
+    }
+}
+
+// the engine plays both sides with the same limits for every move. games still going after
+// max_plies are unfinished, the board doesn't track repetitions or the fifty move rule
+pub fn self_play(
+    board: &BoardState,
+    limits: SearchLimits,
+    options: &EngineOptions,
+    max_plies: usize,
+) -> SelfPlayGame {
+    let tt = Arc::new(TranspositionTable::new(options.hash_mb));
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut game_board = board.clone();
+    let mut game = SelfPlayGame {
+        start: board.clone(),
+        moves: vec![],
+        evals: vec![],
+        result: "*".to_string(),
+    };
+
+    while game.moves.len() < max_plies {
+        if game_board.generate_legal_moves().is_empty() {
+            game.result = final_result(&mut game_board).to_string();
+            break;
+        }
+
+        // the main thread sets the flag when it's done, so it's cleared for every move
+        stop.store(false, Ordering::Relaxed);
+
+        let result = lazy_smp(
+            &game_board,
+            limits,
+            options,
+            Arc::clone(&tt),
+            Arc::clone(&stop),
+        );
+
+        let Some(best_move) = result.best_move else {
+            break;
+        };
+
+        game_board.make_move(best_move);
+        game.moves.push(best_move);
+        game.evals.push(MoveEval {
+            score: result.score,
+            depth: result.depth,
+        });
+    }
+
+    game
+}
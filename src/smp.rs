@@ -0,0 +1,98 @@
+use crate::{
+    board::BoardState,
+    eval::PieceSquareTables,
+    options::EngineOptions,
+    search::{Search, SearchLimits, SearchResult},
+    tt::TranspositionTable,
+};
+use std::{
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+// lazy smp, every thread searches its own copy of the board and they only share the tt.
+// every thread watches the limits, nodes are counted over all of them. only the main thread
+// searches multipv lines and the helpers stop once it's done. the stop flag isn't cleared
+// here, callers reset it when they start a new search so an early stop isn't lost
+pub fn lazy_smp(
+    board: &BoardState,
+    limits: SearchLimits,
+    options: &EngineOptions,
+    tt: Arc<TranspositionTable>,
+    stop: Arc<AtomicBool>,
+) -> SearchResult {
+    let shared_nodes = Arc::new(AtomicU64::new(0));
+    let psq_tables = Arc::new(PieceSquareTables::new(&options.eval_params));
+
+    let results: Vec<SearchResult> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..options.threads.max(1))
+            .map(|thread_id| {
+                let mut thread_board = board.clone();
+
+                thread_board.set_psq_tables(Arc::clone(&psq_tables));
+                thread_board.set_network(options.active_network());
+                let thread_tt = Arc::clone(&tt);
+                let thread_stop = Arc::clone(&stop);
+                let thread_params = options.eval_params.clone();
+                let thread_nodes = Arc::clone(&shared_nodes);
+
+                let multipv = match thread_id {
+                    0 => options.multipv,
+                    _ => 1,
+                };
+
+                scope.spawn(move || {
+                    let mut search =
+                        Search::with_tt(thread_tt, thread_id, Arc::clone(&thread_stop));
+
+                    search.multipv = multipv;
+                    search.shared_nodes = thread_nodes;
+                    search.params = thread_params;
+
+                    let result = search.search(&mut thread_board, limits);
+
+                    if thread_id == 0 {
+                        thread_stop.store(true, Ordering::Relaxed);
+                    }
+
+                    result
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.join().unwrap_or_else(|_| {
+                    println!("Search thread panicked!");
+                    exit(1);
+                })
+            })
+            .collect()
+    });
+
+    let nodes = results.iter().map(|result| result.nodes).sum();
+
+    // deepest thread wins, then the better score, main thread on ties.
+    // helpers have a single line, so with multipv the main thread always reports
+    let mut best = &results[0];
+
+    for result in results.iter().skip(1) {
+        if options.multipv <= 1
+            && result.best_move.is_some()
+            && (result.depth > best.depth
+                || (result.depth == best.depth && result.score > best.score))
+        {
+            best = result;
+        }
+    }
+
+    SearchResult {
+        nodes,
+        ..best.clone()
+    }
+}
@@ -1,8 +1,55 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use crate::{
     board::{BoardState, Color, Piece},
+    book_builder::{build_book, collect_book_moves, BookFilter},
     constants::{BOARD_SQUARES, DEFAULT_FEN_STRING},
+    endgame::{
+        kpk_score, probe_endgame, EndgameResult, KNOWN_WIN, OPPOSITE_BISHOPS_SCALE, PAWNLESS_SCALE,
+        SCALE_NORMAL,
+    },
+    epd::{parse_epd_line, EpdOutcome},
+    eval::{evaluate, game_phase, taper, trace, EvalTerm, CHECKMATE, DRAW, TOTAL_PHASE},
+    eval_params::EvalParams,
+    king_safety::king_safety_score,
+    kpk::kpk_probe,
+    mobility::mobility_score,
+    nnue::{
+        clipped_dot, clipped_dot_scalar, Accumulator, Network, NNUE_INPUTS, NNUE_MAGIC,
+        NNUE_VERSION,
+    },
+    options::EngineOptions,
+    pawn_hash::PawnHashTable,
+    pawn_structure::{passed_pawn_score, passed_pawns, pawn_only_score},
+    pgn::{parse_pgn, write_pgn, MoveEval, PgnHeader, PgnMove},
+    piece_terms::{piece_terms_score, TRAPPED_BISHOP_PENALTY},
+    polyglot::{book_move_to_string, encode_book_move, polyglot_key, Book, BookEntry, BookMode},
+    san::{move_to_san, parse_san},
+    search::{extension, is_recapture, Search, SearchLimits, MAX_DEPTH},
+    see::{see, see_ge},
+    smp::lazy_smp,
+    tt::{Bound, TranspositionTable},
+    tune::{params_to_rust, parse_tuning_line, sigmoid},
+    utils::move_to_string,
+    zobrist::{compute_key, compute_pawn_key},
 };
 
+// full pawn structure of one color without the pawn hash table
+fn pawn_structure_score(board: &BoardState, color: Color) -> (i32, i32) {
+    let params = EvalParams::default();
+    let (pawns_middlegame, pawns_endgame) = pawn_only_score(board, color, &params);
+    let (passed_middlegame, passed_endgame) =
+        passed_pawn_score(board, color, passed_pawns(board, color), &params);
+
+    (
+        pawns_middlegame + passed_middlegame,
+        pawns_endgame + passed_endgame,
+    )
+}
+
 #[test]
 fn from_fen_ok() {
     assert!(BoardState::from_fen(DEFAULT_FEN_STRING).is_ok())
@@ -37,3 +84,1576 @@ fn test_undo() {
 
     matches!(board, prev_board);
 }
+
+#[test]
+fn key_after_undo() {
+    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let initial_key = board.key;
+
+    let white_moves = board.generate_moves_by_color(&Color::White);
+
+    for piece_move in white_moves.iter() {
+        board.make_move(*piece_move);
+
+        assert_eq!(board.key, compute_key(&board));
+
+        let _ = board.undo_move();
+
+        assert_eq!(board.key, initial_key);
+    }
+}
+
+#[test]
+fn pawn_key_after_undo() {
+    // captures of pawns and by pawns on both sides
+    let mut board = BoardState::from_fen("4k3/8/3p4/2n1p3/3P4/5N2/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    let initial_pawn_key = board.pawn_key;
+
+    let white_moves = board.generate_moves_by_color(&Color::White);
+
+    for piece_move in white_moves.iter() {
+        board.make_move(*piece_move);
+
+        assert_eq!(board.pawn_key, compute_pawn_key(&board));
+
+        let _ = board.undo_move();
+
+        assert_eq!(board.pawn_key, initial_pawn_key);
+    }
+}
+
+#[test]
+fn psq_totals_after_moves() {
+    let mut board = BoardState::from_fen("4k3/8/3p4/2n1p3/3P4/5N2/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    let initial = (board.material, board.piece_square);
+
+    for piece_move in board.generate_moves_by_color(&Color::White).iter() {
+        board.make_move(*piece_move);
+
+        assert_eq!(
+            (board.material, board.piece_square),
+            board.psq_tables.totals(&board)
+        );
+
+        let _ = board.undo_move();
+
+        assert_eq!((board.material, board.piece_square), initial);
+    }
+}
+
+#[test]
+fn extensions() {
+    assert_eq!(extension(true, false, false, 2), 1);
+    assert_eq!(extension(false, true, false, 2), 1);
+    assert_eq!(extension(false, false, true, 2), 1);
+    assert_eq!(extension(true, true, true, 2), 1);
+    assert_eq!(extension(false, false, false, 2), 0);
+    // line ran out of extensions
+    assert_eq!(extension(true, true, true, 0), 0);
+}
+
+#[test]
+fn recapture() {
+    let mut board = BoardState::from_fen("4k3/8/3p4/4p3/3P4/8/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    // d4 takes e5, d6 takes back
+    let capture = board
+        .encode_move(35, 28, Piece::Pawn, Color::White, Piece::Pawn, Piece::None)
+        .unwrap();
+    board.make_move(capture);
+
+    let recapture = board
+        .encode_move(19, 28, Piece::Pawn, Color::Black, Piece::Pawn, Piece::None)
+        .unwrap();
+    let quiet = board
+        .encode_move(19, 27, Piece::Pawn, Color::Black, Piece::None, Piece::None)
+        .unwrap();
+
+    assert!(is_recapture(&board, recapture));
+    assert!(!is_recapture(&board, quiet));
+}
+
+#[test]
+fn mate_in_one() {
+    let mut board =
+        BoardState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").expect("Fail during board setup");
+    let mut search = Search::with_tt(
+        Arc::new(TranspositionTable::new(1)),
+        0,
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    let result = search.search(&mut board, SearchLimits::depth(2));
+
+    assert_eq!(result.score, CHECKMATE - 1);
+    assert_eq!(
+        result.best_move,
+        board
+            .encode_move(56, 0, Piece::Rook, Color::White, Piece::None, Piece::None)
+            .ok()
+    );
+}
+
+#[test]
+fn see_defended_pawn() {
+    let board = BoardState::from_fen("4k3/8/3p4/4p3/8/8/4Q3/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    // Qxe5 dxe5
+    let capture = board
+        .encode_move(52, 28, Piece::Queen, Color::White, Piece::Pawn, Piece::None)
+        .unwrap();
+
+    assert_eq!(see(&board, capture), 100 - 900);
+    assert!(!see_ge(&board, capture, 0));
+}
+
+#[test]
+fn see_undefended_pawn() {
+    let board =
+        BoardState::from_fen("4k3/8/8/4p3/8/8/4Q3/4K3 w - - 0 1").expect("Fail during board setup");
+
+    let capture = board
+        .encode_move(52, 28, Piece::Queen, Color::White, Piece::Pawn, Piece::None)
+        .unwrap();
+
+    assert_eq!(see(&board, capture), 100);
+    assert!(see_ge(&board, capture, 100));
+}
+
+#[test]
+fn see_x_ray() {
+    let board = BoardState::from_fen("4k3/4r3/8/4p3/8/8/4R3/4R1K1 w - - 0 1")
+        .expect("Fail during board setup");
+
+    // Rxe5 Rxe5 Rxe5, second rook joins through the first one
+    let capture = board
+        .encode_move(52, 28, Piece::Rook, Color::White, Piece::Pawn, Piece::None)
+        .unwrap();
+
+    assert_eq!(see(&board, capture), 100);
+}
+
+#[test]
+fn lazy_smp_mate_in_one() {
+    let board =
+        BoardState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").expect("Fail during board setup");
+
+    let result = lazy_smp(
+        &board,
+        SearchLimits::depth(2),
+        &EngineOptions {
+            threads: 4,
+            ..Default::default()
+        },
+        Arc::new(TranspositionTable::new(1)),
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    assert_eq!(result.score, CHECKMATE - 1);
+    assert_eq!(
+        result.best_move,
+        board
+            .encode_move(56, 0, Piece::Rook, Color::White, Piece::None, Piece::None)
+            .ok()
+    );
+}
+
+#[test]
+fn tt_store_probe() {
+    let tt = TranspositionTable::new(1);
+
+    tt.store(12345, Some(777), -250, 4, Bound::Lower);
+
+    let entry = tt.probe(12345).expect("Entry should be stored");
+
+    assert_eq!(entry.best_move, Some(777));
+    assert_eq!(entry.score, -250);
+    assert_eq!(entry.depth, 4);
+    assert_eq!(entry.bound, Bound::Lower);
+    assert!(tt.probe(54321).is_none());
+}
+
+#[test]
+fn node_limit() {
+    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let mut search = Search::with_tt(
+        Arc::new(TranspositionTable::new(1)),
+        0,
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    let result = search.search(
+        &mut board,
+        SearchLimits {
+            nodes: Some(2000),
+            ..Default::default()
+        },
+    );
+
+    // stopped early, but a move from a finished iteration is kept
+    assert!(result.nodes <= 2000);
+    assert!(result.depth < 10);
+    assert!(result.best_move.is_some());
+}
+
+#[test]
+fn stop_flag() {
+    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let stop = Arc::new(AtomicBool::new(true));
+    let mut search = Search::with_tt(Arc::new(TranspositionTable::new(1)), 0, stop);
+
+    let result = search.search(
+        &mut board,
+        SearchLimits {
+            infinite: true,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(result.depth, 0);
+    assert_eq!(result.nodes, 0);
+}
+
+#[test]
+fn lazy_smp_limits() {
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let options = EngineOptions {
+        threads: 3,
+        ..Default::default()
+    };
+
+    // nodes of the helpers count too, each thread is at most 1024 nodes behind the others
+    let result = lazy_smp(
+        &board,
+        SearchLimits {
+            nodes: Some(5000),
+            ..Default::default()
+        },
+        &options,
+        Arc::new(TranspositionTable::new(1)),
+        Arc::new(AtomicBool::new(false)),
+    );
+    assert!(result.nodes < 5000 + 3 * 1024);
+
+    // a stop sent before the threads start isn't lost
+    let result = lazy_smp(
+        &board,
+        SearchLimits {
+            infinite: true,
+            ..Default::default()
+        },
+        &options,
+        Arc::new(TranspositionTable::new(1)),
+        Arc::new(AtomicBool::new(true)),
+    );
+    assert_eq!(result.depth, 0);
+}
+
+#[test]
+fn infinite_waits_for_stop() {
+    // black is mated, so every iteration is over at once and MAX_DEPTH comes quickly
+    let mut board =
+        BoardState::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").expect("Fail during board setup");
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut search = Search::with_tt(Arc::new(TranspositionTable::new(1)), 0, Arc::clone(&stop));
+
+    let handle = std::thread::spawn(move || {
+        search.search(
+            &mut board,
+            SearchLimits {
+                infinite: true,
+                ..Default::default()
+            },
+        )
+    });
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert!(!handle.is_finished());
+
+    stop.store(true, Ordering::Relaxed);
+    let result = handle.join().expect("Search thread panicked");
+    assert_eq!(result.depth, MAX_DEPTH);
+}
+
+#[test]
+fn mate_limit() {
+    let board =
+        BoardState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").expect("Fail during board setup");
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let result = lazy_smp(
+        &board,
+        SearchLimits {
+            mate: Some(1),
+            ..Default::default()
+        },
+        &EngineOptions {
+            threads: 2,
+            ..Default::default()
+        },
+        Arc::new(TranspositionTable::new(1)),
+        Arc::clone(&stop),
+    );
+
+    assert_eq!(result.score, CHECKMATE - 1);
+    assert_eq!(result.depth, 1);
+    // main thread signals helpers once it's done
+    assert!(stop.load(Ordering::Relaxed));
+}
+
+#[test]
+fn multipv() {
+    let board =
+        BoardState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").expect("Fail during board setup");
+
+    let result = lazy_smp(
+        &board,
+        SearchLimits::depth(2),
+        &EngineOptions {
+            multipv: 3,
+            ..Default::default()
+        },
+        Arc::new(TranspositionTable::new(1)),
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    assert_eq!(result.pv_lines.len(), 3);
+    assert_eq!(result.pv_lines[0].score, CHECKMATE - 1);
+    assert_eq!(result.pv_lines[0].moves[0], result.best_move.unwrap());
+
+    // every line starts with a different move and they are sorted by score
+    for index in 1..3 {
+        assert!(result.pv_lines[index].score < CHECKMATE - 1);
+        assert!(result.pv_lines[index].score <= result.pv_lines[index - 1].score);
+        assert!(result.pv_lines[..index]
+            .iter()
+            .all(|line| line.moves[0] != result.pv_lines[index].moves[0]));
+    }
+}
+
+#[test]
+fn multipv_more_than_legal_moves() {
+    // king in the corner with only two legal moves
+    let board =
+        BoardState::from_fen("7k/8/5Q2/8/8/8/8/K7 b - - 0 1").expect("Fail during board setup");
+
+    let result = lazy_smp(
+        &board,
+        SearchLimits::depth(1),
+        &EngineOptions {
+            multipv: 4,
+            ..Default::default()
+        },
+        Arc::new(TranspositionTable::new(1)),
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    assert_eq!(result.pv_lines.len(), 2);
+}
+
+#[test]
+fn game_phase_bounds() {
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    assert_eq!(game_phase(&board), TOTAL_PHASE);
+
+    let board = BoardState::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    assert_eq!(game_phase(&board), 0);
+
+    assert_eq!(taper(100, 200, TOTAL_PHASE), 100);
+    assert_eq!(taper(100, 200, 0), 200);
+    assert_eq!(taper(100, 200, TOTAL_PHASE / 2), 150);
+}
+
+#[test]
+fn eval_symmetric() {
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    assert_eq!(
+        evaluate(&board, &mut PawnHashTable::new(1), &EvalParams::default()),
+        0
+    );
+}
+
+#[test]
+fn eval_continuous() {
+    // trading the last minor pieces used to flip the whole eval into endgame mode
+    let before = BoardState::from_fen("4k3/pppn1ppp/8/8/8/8/PPPN1PPP/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    let after = BoardState::from_fen("4k3/ppp2ppp/8/8/8/8/PPP2PPP/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert_eq!(
+        evaluate(&before, &mut PawnHashTable::new(1), &EvalParams::default()),
+        0
+    );
+    assert_eq!(
+        evaluate(&after, &mut PawnHashTable::new(1), &EvalParams::default()),
+        0
+    );
+
+    // a lone extra knight is worth about a knight whatever the phase
+    let extra_knight = BoardState::from_fen("4k3/ppp2ppp/8/8/8/8/PPPN1PPP/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    let score = evaluate(
+        &extra_knight,
+        &mut PawnHashTable::new(1),
+        &EvalParams::default(),
+    );
+
+    assert!((200..450).contains(&score));
+}
+
+#[test]
+fn passed_pawn_detection() {
+    // d5 and h3 are passed, a7 and b4 stop each other
+    let board = BoardState::from_fen("4k3/p7/8/3P4/1P6/7p/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert_eq!(passed_pawns(&board, Color::White), BOARD_SQUARES[27]);
+    assert_eq!(passed_pawns(&board, Color::Black), BOARD_SQUARES[47]);
+}
+
+#[test]
+fn pawn_structure_terms() {
+    // healthy chain against doubled isolated pawns
+    let chain =
+        BoardState::from_fen("4k3/8/8/8/8/2P5/1P6/4K3 w - - 0 1").expect("Fail during board setup");
+    let doubled =
+        BoardState::from_fen("4k3/8/8/8/8/1P6/1P6/4K3 w - - 0 1").expect("Fail during board setup");
+
+    let chain_score = pawn_structure_score(&chain, Color::White);
+    let doubled_score = pawn_structure_score(&doubled, Color::White);
+
+    assert!(chain_score.0 > doubled_score.0);
+    assert!(chain_score.1 > doubled_score.1);
+
+    // further advanced passed pawn is worth more, a blocked one less
+    let far =
+        BoardState::from_fen("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1").expect("Fail during board setup");
+    let near =
+        BoardState::from_fen("4k3/8/8/8/8/3P4/8/4K3 w - - 0 1").expect("Fail during board setup");
+    let blocked =
+        BoardState::from_fen("4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1").expect("Fail during board setup");
+
+    assert!(
+        pawn_structure_score(&far, Color::White).1 > pawn_structure_score(&near, Color::White).1
+    );
+    assert!(
+        pawn_structure_score(&far, Color::White).1 > pawn_structure_score(&blocked, Color::White).1
+    );
+}
+
+#[test]
+fn pawn_structure_symmetric() {
+    let board = BoardState::from_fen("4k3/pp3p1p/2p3p1/3p4/3P4/2P3P1/PP3P1P/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert_eq!(
+        pawn_structure_score(&board, Color::White),
+        pawn_structure_score(&board, Color::Black)
+    );
+}
+
+#[test]
+fn pawn_hash_matches_full_eval() {
+    let board = BoardState::from_fen("4k3/pp3p1p/2p3p1/3p4/3P4/2P3P1/PP1N1P1P/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    let mut pawn_table = PawnHashTable::new(1);
+
+    let first = evaluate(&board, &mut pawn_table, &EvalParams::default());
+
+    assert!(pawn_table.probe(board.pawn_key).is_some());
+    // second call hits the table
+    assert_eq!(
+        evaluate(&board, &mut pawn_table, &EvalParams::default()),
+        first
+    );
+    assert_eq!(
+        evaluate(&board, &mut PawnHashTable::new(1), &EvalParams::default()),
+        first
+    );
+}
+
+#[test]
+fn mobility_terms() {
+    // the same knight in the corner against in the center
+    let corner =
+        BoardState::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").expect("Fail during board setup");
+    let center =
+        BoardState::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").expect("Fail during board setup");
+    // center squares covered by black pawns
+    let covered = BoardState::from_fen("4k3/8/2p1p3/1p3p2/3N4/1p3p2/2p1p3/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert!(
+        mobility_score(&center, Color::White, &EvalParams::default()).0
+            > mobility_score(&corner, Color::White, &EvalParams::default()).0
+    );
+    assert!(
+        mobility_score(&center, Color::White, &EvalParams::default()).0
+            > mobility_score(&covered, Color::White, &EvalParams::default()).0
+    );
+
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+
+    assert_eq!(
+        mobility_score(&board, Color::White, &EvalParams::default()),
+        mobility_score(&board, Color::Black, &EvalParams::default())
+    );
+}
+
+#[test]
+fn king_safety_terms() {
+    let shield =
+        BoardState::from_fen("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1").expect("Fail during board setup");
+    let broken =
+        BoardState::from_fen("6k1/8/8/8/8/8/5P1P/6K1 w - - 0 1").expect("Fail during board setup");
+    let stormed = BoardState::from_fen("6k1/8/8/8/8/6p1/5PPP/6K1 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert!(
+        king_safety_score(&shield, Color::White, &EvalParams::default())
+            > king_safety_score(&broken, Color::White, &EvalParams::default())
+    );
+    assert!(
+        king_safety_score(&shield, Color::White, &EvalParams::default())
+            > king_safety_score(&stormed, Color::White, &EvalParams::default())
+    );
+
+    // queen and knight close in on the king together
+    let attacked = BoardState::from_fen("6k1/8/8/8/7q/5n2/5PPP/6K1 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert!(
+        king_safety_score(&shield, Color::White, &EvalParams::default())
+            > king_safety_score(&attacked, Color::White, &EvalParams::default())
+    );
+
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+
+    assert_eq!(
+        king_safety_score(&board, Color::White, &EvalParams::default()),
+        king_safety_score(&board, Color::Black, &EvalParams::default())
+    );
+}
+
+#[test]
+fn piece_terms() {
+    let open_file =
+        BoardState::from_fen("4k3/p7/8/8/8/8/P7/3RK3 w - - 0 1").expect("Fail during board setup");
+    let closed_file =
+        BoardState::from_fen("4k3/p7/8/8/8/8/P7/R3K3 w - - 0 1").expect("Fail during board setup");
+
+    assert!(
+        piece_terms_score(&open_file, Color::White, &EvalParams::default()).0
+            > piece_terms_score(&closed_file, Color::White, &EvalParams::default()).0
+    );
+
+    // knight on d5 defended by e4 with no black pawns on c or e files to chase it
+    let outpost = BoardState::from_fen("4k3/p7/8/3N4/4P3/8/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+    let chased = BoardState::from_fen("4k3/p3p3/8/3N4/4P3/8/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert!(
+        piece_terms_score(&outpost, Color::White, &EvalParams::default()).0
+            > piece_terms_score(&chased, Color::White, &EvalParams::default()).0
+    );
+
+    let trapped =
+        BoardState::from_fen("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1").expect("Fail during board setup");
+
+    assert!(
+        piece_terms_score(&trapped, Color::White, &EvalParams::default()).0
+            <= TRAPPED_BISHOP_PENALTY.0
+    );
+
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+
+    assert_eq!(
+        piece_terms_score(&board, Color::White, &EvalParams::default()),
+        piece_terms_score(&board, Color::Black, &EvalParams::default())
+    );
+}
+
+#[test]
+fn eval_trace() {
+    let board =
+        BoardState::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 4")
+            .expect("Fail during board setup");
+    let eval_trace = trace(&board, &mut PawnHashTable::new(1), &EvalParams::default());
+
+    // black to move, so evaluate is from black's side
+    assert_eq!(
+        eval_trace.score(),
+        -evaluate(&board, &mut PawnHashTable::new(1), &EvalParams::default())
+    );
+    assert_eq!(eval_trace.phase, game_phase(&board));
+    assert_eq!(eval_trace.term_score(EvalTerm::Material), (0, 0));
+}
+
+fn endgame_score(fen: &str) -> i32 {
+    let board = BoardState::from_fen(fen).expect("Fail during board setup");
+
+    match probe_endgame(&board) {
+        Some((_, EndgameResult::Score(score))) => score,
+        _ => panic!("No endgame score for {}", fen),
+    }
+}
+
+#[test]
+fn endgame_evaluators() {
+    let probe =
+        |fen: &str| probe_endgame(&BoardState::from_fen(fen).expect("Fail during board setup"));
+
+    assert_eq!(probe(DEFAULT_FEN_STRING), None);
+    assert_eq!(
+        probe("8/8/4k3/8/8/3NK3/8/8 w - - 0 1"),
+        Some(("KmK", EndgameResult::Score(0)))
+    );
+    assert_eq!(
+        probe("8/8/4k3/3r4/8/3RK3/8/8 w - - 0 1"),
+        Some(("pawnless", EndgameResult::Scale(PAWNLESS_SCALE)))
+    );
+    assert_eq!(
+        probe("8/5b2/4k1p1/8/8/3KB3/5P2/8 w - - 0 1"),
+        Some(("KBKB", EndgameResult::Scale(OPPOSITE_BISHOPS_SCALE)))
+    );
+
+    // the lone king is worse off in the corner, for both colors
+    let corner = endgame_score("k7/8/8/8/8/8/8/3QK3 w - - 0 1");
+    assert!(corner > KNOWN_WIN);
+    assert!(corner > endgame_score("8/8/8/3k4/8/8/8/3QK3 w - - 0 1"));
+    assert_eq!(corner, -endgame_score("3qk3/8/8/8/8/8/8/K7 b - - 0 1"));
+
+    // two bishops only mate on both colors
+    assert!(endgame_score("k7/8/8/8/8/8/8/2B1KB2 w - - 0 1") > KNOWN_WIN);
+    assert_eq!(
+        probe("k7/8/8/8/8/8/8/2B1K1B1 w - - 0 1"),
+        Some(("KXK", EndgameResult::Score(0)))
+    );
+    assert_eq!(
+        probe("k7/8/8/8/8/8/4P3/2B1K1B1 w - - 0 1"),
+        Some(("KXK", EndgameResult::Scale(SCALE_NORMAL)))
+    );
+
+    // light squared bishop mates on a8 or h1
+    assert!(
+        endgame_score("k7/8/8/8/8/8/8/3BKN2 w - - 0 1")
+            > endgame_score("7k/8/8/8/8/8/8/3BKN2 w - - 0 1")
+    );
+
+    // the black king can't catch the pawn
+    assert!(endgame_score("8/8/8/8/7k/8/P7/4K3 w - - 0 1") > KNOWN_WIN);
+
+    // rook pawn on the seventh next to its king draws against the queen
+    assert!(endgame_score("8/8/8/8/8/7Q/p7/1k5K w - - 0 1") < KNOWN_WIN / 2);
+    assert!(endgame_score("8/8/8/8/8/7Q/3p4/2k4K w - - 0 1") > KNOWN_WIN / 2);
+
+    // the king in front of the pawn wins, far away from it with the pawn advanced it doesn't
+    assert!(endgame_score("R7/8/8/8/3p1k2/8/3K4/8 w - - 0 1") > 400);
+    assert!(endgame_score("R7/8/8/8/8/8/2kp4/7K w - - 0 1") < 100);
+
+    // evaluate is from the side to move
+    let board =
+        BoardState::from_fen("k7/8/8/8/8/8/8/3QK3 b - - 0 1").expect("Fail during board setup");
+    assert_eq!(
+        evaluate(&board, &mut PawnHashTable::new(1), &EvalParams::default()),
+        -corner
+    );
+}
+
+#[test]
+fn kpk_bitbase() {
+    // king on the sixth in front of the pawn wins whoever moves
+    assert!(kpk_probe(20, 28, 4, Color::White));
+    assert!(kpk_probe(20, 28, 4, Color::Black));
+
+    // rook pawn with the defending king in the corner
+    assert!(!kpk_probe(56, 48, 0, Color::White));
+    assert!(!kpk_probe(56, 48, 0, Color::Black));
+
+    // whoever has the opposition decides it
+    assert!(!kpk_probe(27, 35, 11, Color::White));
+    assert!(kpk_probe(27, 35, 11, Color::Black));
+
+    // the same with black as the side with the pawn, from the side to move
+    let board =
+        BoardState::from_fen("8/8/8/3p4/3k4/8/3K4/8 b - - 0 1").expect("Fail during board setup");
+    assert_eq!(kpk_score(&board), Some(DRAW));
+
+    let board =
+        BoardState::from_fen("8/8/8/3p4/3k4/8/3K4/8 w - - 0 1").expect("Fail during board setup");
+    assert!(kpk_score(&board).expect("No kpk score") < -KNOWN_WIN);
+    assert_eq!(
+        evaluate(&board, &mut PawnHashTable::new(1), &EvalParams::default()),
+        kpk_score(&board).expect("No kpk score")
+    );
+}
+
+// generated move by its coordinate notation
+fn find_move(board: &BoardState, notation: &str) -> u32 {
+    board
+        .generate_moves_by_color(&board.to_move)
+        .into_iter()
+        .find(|piece_move| move_to_string(*piece_move) == notation)
+        .unwrap_or_else(|| panic!("No move {}", notation))
+}
+
+#[test]
+fn polyglot_reference_keys() {
+    let board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    assert_eq!(polyglot_key(&board), 0x463B96181691FC9C);
+
+    // keys of the polyglot format description, moves from the start position
+    for (moves, key) in [
+        ("e2e4", 0x823C9B50FD114196),
+        ("e2e4 d7d5", 0x0756B94461C50FB0),
+        ("e2e4 d7d5 e4e5", 0x662FAFB965DB29D4),
+        ("e2e4 d7d5 e4e5 f7f5", 0x22A48B5A8E47FF78),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652A607CA3F242C1),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00FDD303C946BDD9),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3C8123EA7B067637),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5C3F9B829B279560),
+    ] {
+        let mut board = board.clone();
+
+        for notation in moves.split_whitespace() {
+            board.make_move(find_move(&board, notation));
+        }
+
+        assert_eq!(polyglot_key(&board), key, "{}", moves);
+    }
+}
+
+#[test]
+fn polyglot_book_file() {
+    // a book in the polyglot format, written from the published keys and not by the engine
+    let book = Book::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/polyglot/openings.bin"
+    ))
+    .expect("Fail during book loading");
+    assert_eq!(book.entries.len(), 6);
+
+    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let book_moves = |board: &BoardState| -> Vec<(String, u16)> {
+        book.book_moves(board)
+            .into_iter()
+            .map(|(piece_move, weight)| (move_to_string(piece_move), weight))
+            .collect()
+    };
+
+    assert_eq!(
+        book_moves(&board),
+        vec![
+            ("e2e4".to_string(), 20),
+            ("d2d4".to_string(), 15),
+            ("g1f3".to_string(), 5)
+        ]
+    );
+
+    for (notation, best) in [("e2e4", "c7c5"), ("d7d5", "e4d5")] {
+        board.make_move(find_move(&board, notation));
+
+        let piece_move = book
+            .pick_move(&board, BookMode::Best)
+            .expect("Fail during book lookup");
+        assert_eq!(move_to_string(piece_move), best);
+    }
+
+    board.make_move(find_move(&board, "e4d5"));
+    assert_eq!(book.pick_move(&board, BookMode::Random), None);
+}
+
+#[test]
+fn polyglot_book() {
+    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let start_key = polyglot_key(&board);
+
+    // the en passant square after e4 doesn't count, no black pawn can take there
+    board.make_move(find_move(&board, "e2e4"));
+    assert_eq!(
+        polyglot_key(&board),
+        polyglot_key(
+            &BoardState::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                .expect("Fail during board setup")
+        )
+    );
+    assert_ne!(
+        polyglot_key(
+            &BoardState::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                .expect("Fail during board setup")
+        ),
+        polyglot_key(
+            &BoardState::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                .expect("Fail during board setup")
+        )
+    );
+    let _ = board.undo_move();
+    assert_eq!(polyglot_key(&board), start_key);
+
+    let e2e4 = find_move(&board, "e2e4");
+    let d2d4 = find_move(&board, "d2d4");
+    assert_eq!(book_move_to_string(encode_book_move(e2e4)), "e2e4");
+
+    let mut special_board = BoardState::from_fen("2k5/4P3/8/8/8/8/8/R3K2R w KQ - 0 1")
+        .expect("Fail during board setup");
+    for (san, book_move) in [("O-O", "e1h1"), ("O-O-O", "e1a1"), ("e8=N", "e7e8n")] {
+        let piece_move = parse_san(&mut special_board, san).expect("Fail during san parsing");
+
+        assert_eq!(book_move_to_string(encode_book_move(piece_move)), book_move);
+    }
+
+    let entry = |raw_move, weight| BookEntry {
+        key: start_key,
+        raw_move,
+        weight,
+        learn: 0,
+    };
+    let mut bytes = vec![];
+    for book_entry in [
+        entry(encode_book_move(e2e4), 10),
+        entry(encode_book_move(d2d4), 30),
+        // castling, written as king takes rook, isn't legal in the start position
+        entry(0x0107, 5),
+    ] {
+        assert_eq!(BookEntry::from_bytes(&book_entry.to_bytes()), book_entry);
+        bytes.extend(book_entry.to_bytes());
+    }
+
+    let book = Book::from_bytes(&bytes).expect("Fail during book parsing");
+    assert_eq!(book.probe(start_key).len(), 3);
+    assert_eq!(book.book_moves(&board), vec![(e2e4, 10), (d2d4, 30)]);
+    assert_eq!(book.pick_move(&board, BookMode::Best), Some(d2d4));
+    assert!([Some(e2e4), Some(d2d4)].contains(&book.pick_move(&board, BookMode::Random)));
+
+    let empty_board =
+        BoardState::from_fen("8/8/8/3k4/8/8/8/4K3 w - - 0 1").expect("Fail during board setup");
+    assert_eq!(book.pick_move(&empty_board, BookMode::Best), None);
+
+    assert!(Book::from_bytes(&bytes[..20]).is_err());
+    let mut unsorted = entry(0, 1).to_bytes().to_vec();
+    unsorted.extend(
+        BookEntry {
+            key: 0,
+            ..entry(0, 1)
+        }
+        .to_bytes(),
+    );
+    assert!(Book::from_bytes(&unsorted).is_err());
+}
+
+const TEST_PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) Nc6 3. Bb5 $1 a6; the main line
+4. Ba4 Nf6 5. O-O 1-0
+
+[Event "Test"]
+[Result "1/2-1/2"]
+
+1.d4 d5 2.c4 e6 1/2-1/2
+
+[Event "Test"]
+[Result "0-1"]
+
+1. e4 c5 0-1
+"#;
+
+#[test]
+fn san_moves() {
+    let mut board = BoardState::from_fen("r3k2r/8/8/3p4/4P3/5N2/8/RN2K1NR w - - 0 1")
+        .expect("Fail during board setup");
+
+    assert_eq!(
+        move_to_string(parse_san(&mut board, "exd5").expect("Fail during san parsing")),
+        "e4d5"
+    );
+    assert_eq!(
+        move_to_string(parse_san(&mut board, "e5!?").expect("Fail during san parsing")),
+        "e4e5"
+    );
+    assert_eq!(
+        move_to_string(parse_san(&mut board, "Nfd2").expect("Fail during san parsing")),
+        "f3d2"
+    );
+    assert_eq!(parse_san(&mut board, "Nd2"), Err("Ambiguous move!"));
+    assert_eq!(parse_san(&mut board, "Nc4"), Err("Illegal move!"));
+    assert_eq!(
+        parse_san(&mut board, "Zz9"),
+        Err("Incorrect move notation!")
+    );
+    assert!(parse_san(&mut board, "O-O").is_err());
+}
+
+#[test]
+fn pgn_games() {
+    let games = parse_pgn(TEST_PGN).expect("Fail during pgn parsing");
+    let sans = |moves: &[PgnMove]| -> Vec<String> {
+        moves.iter().map(|pgn_move| pgn_move.san.clone()).collect()
+    };
+
+    assert_eq!(games.len(), 3);
+    assert_eq!(games[0].tag("White"), Some("A"));
+    assert_eq!(games[0].result, "1-0");
+    assert_eq!(
+        sans(&games[0].moves),
+        ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"]
+    );
+    assert_eq!(sans(&games[1].moves), ["d4", "d5", "c4", "e6"]);
+
+    // comments, nags and variations stay with the move before them
+    let first_game = &games[0].moves;
+    assert_eq!(first_game[0].comment.as_deref(), Some("best by test"));
+    assert_eq!(first_game[4].nags, [1]);
+    assert_eq!(first_game[5].comment.as_deref(), Some("the main line"));
+    assert_eq!(first_game[2].variations.len(), 1);
+    assert_eq!(sans(&first_game[2].variations[0]), ["f4", "exf4", "Nf3"]);
+    assert_eq!(sans(&first_game[2].variations[0][1].variations[0]), ["d5"]);
+    assert_eq!(first_game[6].line, 7);
+
+    assert!(parse_pgn("1. e4 (1. d4").is_err());
+    assert!(parse_pgn("1. e4 ) e5").is_err());
+    assert!(parse_pgn("1. e4 {no end").is_err());
+
+    // a move number is digits and dots, castling with zeros stays whole
+    let numbers = parse_pgn("1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.0-0 4...Nf6 5 . d3 *")
+        .expect("Fail during pgn parsing");
+    assert_eq!(
+        sans(&numbers[0].moves),
+        ["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "0-0", "Nf6", "d3"]
+    );
+
+    // % only escapes a line from the first column
+    let escaped = parse_pgn("% skipped 1. d4\n1. e4 % e5\n*").expect("Fail during pgn parsing");
+    assert_eq!(sans(&escaped[0].moves), ["e4", "%", "e5"]);
+
+    let suffixes = parse_pgn("1. e4!? e5?? *").expect("Fail during pgn parsing");
+    assert_eq!(sans(&suffixes[0].moves), ["e4", "e5"]);
+    assert_eq!(
+        (&suffixes[0].moves[0].nags, &suffixes[0].moves[1].nags),
+        (&vec![5], &vec![4])
+    );
+}
+
+#[test]
+fn pgn_replay() {
+    let games = parse_pgn(TEST_PGN).expect("Fail during pgn parsing");
+
+    let replayed = games[1].replay().expect("Fail during pgn replay");
+    assert_eq!(replayed.moves.len(), 4);
+    assert_eq!(replayed.positions.len(), 5);
+    assert_eq!(move_to_string(replayed.moves[2]), "c2c4");
+    assert!(matches!(replayed.positions[4].to_move, Color::White));
+    assert_eq!(replayed.positions[4].fullmove, 3);
+
+    let castled = games[0].replay().expect("Fail during pgn replay");
+    assert_eq!(castled.moves.len(), 9);
+    assert!(castled.positions[9]
+        .to_fen()
+        .starts_with("r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq -"));
+
+    // a whole master game with long castling, played through to the mate
+    let opera = parse_pgn(
+        r#"[Event "Paris"]
+[White "Morphy, Paul"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+1.e4 e5 2.Nf3 d6 3.d4 Bg4 4.dxe5 Bxf3 5.Qxf3 dxe5 6.Bc4 Nf6 7.Qb3 Qe7 8.Nc3 c6
+9.Bg5 b5 10.Nxb5 cxb5 11.Bxb5+ Nbd7 12.O-O-O Rd8 13.Rxd7 Rxd7 14.Rd1 Qe6
+15.Bxd7+ Nxd7 16.Qb8+ Nxb8 17.Rd8# 1-0
+"#,
+    )
+    .expect("Fail during pgn parsing");
+    let mut mated = opera[0]
+        .replay()
+        .expect("Fail during pgn replay")
+        .positions
+        .pop()
+        .expect("Fail during pgn replay");
+    assert_eq!(opera[0].moves.len(), 33);
+    assert_eq!(opera[0].result, "1-0");
+    assert!(mated
+        .to_fen()
+        .starts_with("1n1Rkb1r/p4ppp/4q3/4p1B1/4P3/8/PPP2PPP/2K5 b k -"));
+    assert!(mated.is_in_check(&Color::Black));
+    assert!(mated.generate_legal_moves().is_empty());
+
+    // the error points at the move the board can't play
+    let illegal = parse_pgn("1. e4 e5 2. Ke3 *").expect("Fail during pgn parsing");
+    assert_eq!(
+        illegal[0]
+            .replay()
+            .map(|_| ())
+            .map_err(|err| err.to_string()),
+        Err("line 1, 2. Ke3: Illegal move!".to_string())
+    );
+
+    let (partial, err) = illegal[0].replay_partial();
+    assert!(err.is_some());
+    assert_eq!(partial.moves.len(), 2);
+
+    let from_fen = parse_pgn("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n\n1... Kd7 2. e4 *")
+        .expect("Fail during pgn parsing");
+    assert_eq!(
+        from_fen[0]
+            .replay()
+            .expect("Fail during pgn replay")
+            .moves
+            .len(),
+        2
+    );
+
+    // positions taken from later in a game keep their move number
+    let midgame = parse_pgn("[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 12 40\"]\n\n40. e4 Kd7 *")
+        .expect("Fail during pgn parsing");
+    let replayed = midgame[0].replay().expect("Fail during pgn replay");
+    assert_eq!(replayed.moves.len(), 2);
+    assert_eq!(replayed.positions[0].fullmove, 40);
+    assert_eq!(replayed.positions[2].fullmove, 41);
+}
+
+#[test]
+fn book_from_pgn() {
+    let games = parse_pgn(TEST_PGN).expect("Fail during pgn parsing");
+    let filter = BookFilter::default();
+    let stats = collect_book_moves(&games, &filter);
+
+    assert_eq!((stats.games, stats.cut_short), (3, 0));
+
+    // the book goes through a file like with make_book
+    let book_path = std::env::temp_dir().join(format!("ravissant-{}.bin", std::process::id()));
+    let book_path = book_path.to_str().expect("Fail during path setup");
+    let built = build_book(&stats, &filter);
+
+    built.save(book_path).expect("Fail during book saving");
+    let book = Book::load(book_path).expect("Fail during book loading");
+    let _ = std::fs::remove_file(book_path);
+
+    assert_eq!(book.entries, built.entries);
+    let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let e2e4 = find_move(&board, "e2e4");
+    let d2d4 = find_move(&board, "d2d4");
+
+    // e4 won once and lost once, d4 drew
+    assert_eq!(book.book_moves(&board), vec![(e2e4, 2), (d2d4, 1)]);
+
+    // black lost after e5 and the winning c5 is the only move after e4
+    board.make_move(e2e4);
+    assert_eq!(
+        book.book_moves(&board),
+        vec![(find_move(&board, "c7c5"), 2)]
+    );
+
+    // games go on past castling, written as the king taking its rook
+    let mut castling_board =
+        games[0].replay().expect("Fail during pgn replay").positions[8].clone();
+    let castling = parse_san(&mut castling_board, "O-O").expect("Fail during san parsing");
+    assert_eq!(book.book_moves(&castling_board), vec![(castling, 2)]);
+    assert_eq!(book_move_to_string(encode_book_move(castling)), "e1h1");
+
+    let only_draws = BookFilter {
+        result: Some("1/2-1/2".to_string()),
+        ..BookFilter::default()
+    };
+    assert_eq!(
+        build_book(&collect_book_moves(&games, &only_draws), &only_draws)
+            .entries
+            .len(),
+        4
+    );
+
+    let frequent = BookFilter {
+        min_count: 2,
+        ..BookFilter::default()
+    };
+    assert_eq!(
+        build_book(&collect_book_moves(&games, &frequent), &frequent)
+            .entries
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn tuning_positions() {
+    let position = parse_tuning_line("4k3/8/8/8/8/8/4P3/4K3 w - - 12 40 \"1-0\";")
+        .expect("Fail during tuning position parsing");
+
+    assert_eq!(position.result, 1.0);
+    assert_eq!(
+        parse_tuning_line("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [0.5]")
+            .expect("Fail during tuning position parsing")
+            .result,
+        0.5
+    );
+    assert!(parse_tuning_line("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 win").is_err());
+
+    assert_eq!(sigmoid(0, 1.0), 0.5);
+    assert!(sigmoid(400, 1.0) > 0.9);
+
+    let rust = params_to_rust(&mut EvalParams::default(), 0.0, 0);
+
+    assert!(rust.contains("pub const KNIGHT: i32 = 310;"));
+    assert!(rust.contains("pub const BISHOP_PAIR_BONUS: (i32, i32) = (50, 50);"));
+    assert!(rust.contains("pub const DOUBLED_PAWN_PENALTY: (i32, i32) = (-10, -20);"));
+}
+
+#[test]
+fn tuned_params_match_eval_consts() {
+    let rust = params_to_rust(&mut EvalParams::default(), 0.0, 0);
+    let sources = [
+        include_str!("eval.rs"),
+        include_str!("king_safety.rs"),
+        include_str!("mobility.rs"),
+        include_str!("pawn_structure.rs"),
+        include_str!("piece_terms.rs"),
+    ]
+    .concat();
+
+    // every emitted const replaces a declaration with the same name and type
+    for line in rust.lines().filter(|line| line.starts_with("pub const ")) {
+        let declaration = &line[..line.find(" = ").expect("Fail during const parsing") + 2];
+
+        assert!(sources.contains(declaration), "{}", declaration);
+    }
+
+    // and every const the default params are built from is emitted
+    let eval_params = include_str!("eval_params.rs");
+    let imports = &eval_params[..eval_params
+        .find("};\n")
+        .expect("Fail during import parsing")];
+
+    for name in imports
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_uppercase() || c == '_'))
+    {
+        assert!(rust.contains(&format!("pub const {}: ", name)), "{}", name);
+    }
+}
+
+#[test]
+fn eval_params_text() {
+    let mut params = EvalParams::default();
+
+    params.middlegame_values[1] = 333;
+    params.doubled_pawn = (-1, -2);
+
+    let loaded = EvalParams::from_text(&params.to_text()).expect("Fail during params parsing");
+
+    assert_eq!(loaded, params);
+
+    // missing params keep their defaults
+    let partial = EvalParams::from_text("# comment\nBISHOP_PAIR_BONUS 10 20\n")
+        .expect("Fail during params parsing");
+
+    assert_eq!(partial.bishop_pair, (10, 20));
+    assert_eq!(partial.doubled_pawn, EvalParams::default().doubled_pawn);
+
+    assert!(EvalParams::from_text("NO_SUCH_PARAM 1").is_err());
+    assert!(EvalParams::from_text("BISHOP_PAIR_BONUS 1").is_err());
+
+    let mut options = EngineOptions::default();
+
+    assert!(options.set_option("EvalFile", "/no/such/file").is_err());
+}
+
+// small network with made up weights, nnue tests can't ship a real one
+fn test_network(hidden: usize) -> Network {
+    let weight = |index: usize| ((index * 7919 % 201) as i16) - 100;
+
+    Network {
+        hidden,
+        feature_weights: (0..NNUE_INPUTS * hidden).map(weight).collect(),
+        feature_biases: (0..hidden).map(|index| weight(index) + 60).collect(),
+        output_weights: (0..2 * hidden).map(|index| weight(index * 3)).collect(),
+        output_bias: 1000,
+    }
+}
+
+// writes the network in the weight file format
+fn network_to_bytes(network: &Network) -> Vec<u8> {
+    let mut bytes = NNUE_MAGIC.to_vec();
+
+    bytes.extend(NNUE_VERSION.to_le_bytes());
+    bytes.extend((network.hidden as u32).to_le_bytes());
+
+    for value in network
+        .feature_weights
+        .iter()
+        .chain(network.feature_biases.iter())
+        .chain(network.output_weights.iter())
+    {
+        bytes.extend(value.to_le_bytes());
+    }
+
+    bytes.extend(network.output_bias.to_le_bytes());
+
+    bytes
+}
+
+#[test]
+fn nnue_file_format() {
+    let network = test_network(16);
+    let loaded =
+        Network::from_bytes(&network_to_bytes(&network)).expect("Fail during network loading");
+
+    assert_eq!(loaded.hidden, 16);
+    assert_eq!(loaded.feature_weights, network.feature_weights);
+    assert_eq!(loaded.output_weights, network.output_weights);
+    assert_eq!(loaded.output_bias, network.output_bias);
+
+    assert!(Network::from_bytes(&network_to_bytes(&network)[..100]).is_err());
+    assert!(Network::from_bytes(b"nope").is_err());
+}
+
+#[test]
+fn nnue_incremental_accumulator() {
+    let network = Arc::new(test_network(32));
+    let mut board = BoardState::from_fen("4k3/8/3p4/2n1p3/3P4/5N2/8/4K3 w - - 0 1")
+        .expect("Fail during board setup");
+
+    board.set_network(Some(Arc::clone(&network)));
+
+    let initial = board
+        .nnue
+        .clone()
+        .expect("Fail during network setup")
+        .accumulator;
+
+    for piece_move in board.generate_moves_by_color(&Color::White).iter() {
+        board.make_move(*piece_move);
+
+        let updated = &board
+            .nnue
+            .as_ref()
+            .expect("Fail during network setup")
+            .accumulator;
+
+        assert_eq!(updated.values, Accumulator::new(&network, &board).values);
+
+        let _ = board.undo_move();
+
+        let restored = &board
+            .nnue
+            .as_ref()
+            .expect("Fail during network setup")
+            .accumulator;
+
+        assert_eq!(restored.values, initial.values);
+    }
+
+    // the network is symmetric in its inputs, so a mirrored position scores the same
+    let mirrored = BoardState::from_fen("4k3/8/5n2/3p4/2N1P3/3P4/8/4K3 b - - 0 1")
+        .map(|mut mirrored| {
+            mirrored.set_network(Some(Arc::clone(&network)));
+            mirrored
+        })
+        .expect("Fail during board setup");
+
+    assert_eq!(
+        evaluate(&board, &mut PawnHashTable::new(1), &EvalParams::default()),
+        evaluate(
+            &mirrored,
+            &mut PawnHashTable::new(1),
+            &EvalParams::default()
+        )
+    );
+}
+
+#[test]
+fn nnue_simd_matches_scalar() {
+    let values: Vec<i16> = (0..64)
+        .map(|index| (index * 37 % 600) as i16 - 200)
+        .collect();
+    let weights: Vec<i16> = (0..64)
+        .map(|index| (index * 53 % 400) as i16 - 200)
+        .collect();
+
+    assert_eq!(
+        clipped_dot(&values, &weights),
+        clipped_dot_scalar(&values, &weights)
+    );
+}
+
+#[test]
+fn fen_round_trip() {
+    for fen in [
+        DEFAULT_FEN_STRING,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 40",
+        "8/8/8/8/8/8/8/K6k b - - 0 1",
+    ] {
+        let board = BoardState::from_fen(fen).expect("Fail during board setup");
+
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    // the halfmove toggle isn't a clock, so it's always written as 0
+    let board = BoardState::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 1 40")
+        .expect("Fail during board setup");
+    assert_eq!(board.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 40");
+}
+
+#[test]
+fn pgn_export() {
+    let mut board = BoardState::from_fen("r3k2r/8/8/3p4/4P3/5N2/8/RN2K1NR w - - 0 1")
+        .expect("Fail during board setup");
+
+    // both knights reach d2, the file tells them apart
+    let f3d2 = find_move(&board, "f3d2");
+    assert_eq!(move_to_san(&mut board, f3d2), "Nfd2");
+    let e4d5 = find_move(&board, "e4d5");
+    assert_eq!(move_to_san(&mut board, e4d5), "exd5");
+
+    let mut mate =
+        BoardState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").expect("Fail during board setup");
+    let a1a8 = find_move(&mate, "a1a8");
+    assert_eq!(move_to_san(&mut mate, a1a8), "Ra8#");
+
+    let start = BoardState::from_fen(DEFAULT_FEN_STRING).expect("Fail during board setup");
+    let games = parse_pgn(TEST_PGN).expect("Fail during pgn parsing");
+    let moves = games[1].replay().expect("Fail during pgn replay").moves;
+    let header = PgnHeader {
+        white: "A \"quoted\"".to_string(),
+        result: "1/2-1/2".to_string(),
+        ..PgnHeader::default()
+    };
+
+    let evals: Vec<MoveEval> = (0..moves.len())
+        .map(|index| MoveEval {
+            score: 10 * index as i32 - 15,
+            depth: 5,
+        })
+        .collect();
+    let text = write_pgn(&header, &start, &moves, Some(&evals)).expect("Fail during export");
+
+    assert!(text.contains("[White \"A \\\"quoted\\\"\"]"));
+    assert!(!text.contains("FEN"));
+    assert!(text.ends_with(
+        "1. d4 {-0.15/5} 1... d5 {+0.05/5} 2. c4 {+0.05/5} 2... e6 {-0.15/5} 1/2-1/2\n"
+    ));
+
+    // exported games read back to the same moves
+    let exported = parse_pgn(&text).expect("Fail during pgn parsing");
+    assert_eq!(exported[0].tag("White"), Some("A \"quoted\""));
+    assert_eq!(
+        exported[0].replay().expect("Fail during pgn replay").moves,
+        moves
+    );
+
+    let endgame =
+        BoardState::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").expect("Fail during board setup");
+    let moves = vec![find_move(&endgame, "e8d7")];
+    let text =
+        write_pgn(&PgnHeader::default(), &endgame, &moves, None).expect("Fail during export");
+
+    assert!(text.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]"));
+    assert!(text.ends_with("1... Kd7 *\n"));
+    assert!(write_pgn(&PgnHeader::default(), &start, &moves, None).is_err());
+}
+
+#[test]
+fn san_formatting() {
+    // rooks on one file need the rank, three queens the full square
+    let mut board = BoardState::from_fen("4k3/8/8/Q1Q5/7R/Q7/8/6KR w - - 0 1")
+        .expect("Fail during board setup");
+    let h1h3 = find_move(&board, "h1h3");
+    assert_eq!(move_to_san(&mut board, h1h3), "R1h3");
+    let a5b4 = find_move(&board, "a5b4");
+    assert_eq!(move_to_san(&mut board, a5b4), "Qa5b4");
+    assert_eq!(
+        move_to_string(parse_san(&mut board, "Qa5b4").expect("Fail during san parsing")),
+        "a5b4"
+    );
+    assert_eq!(parse_san(&mut board, "Qb4"), Err("Ambiguous move!"));
+
+    let mut board = BoardState::from_fen("2k5/4P3/8/8/8/8/8/R3K2R w KQ - 0 1")
+        .expect("Fail during board setup");
+
+    for (san, notation) in [
+        ("O-O", "e1g1"),
+        ("O-O-O", "e1c1"),
+        ("e8=Q+", "e7e8q"),
+        ("e8=N", "e7e8n"),
+    ] {
+        let piece_move = parse_san(&mut board, san).expect("Fail during san parsing");
+
+        assert_eq!(move_to_string(piece_move), notation);
+        assert_eq!(move_to_san(&mut board, piece_move), san);
+    }
+
+    assert_eq!(parse_san(&mut board, "e8"), Err("Missing promotion piece!"));
+    assert_eq!(
+        parse_san(&mut board, "Ke2=Q"),
+        Err("Incorrect move notation!")
+    );
+
+    let mut board =
+        BoardState::from_fen("4k3/8/8/8/8/8/8/R3K2R b - - 0 1").expect("Fail during board setup");
+    assert_eq!(parse_san(&mut board, "O-O"), Err("Illegal move!"));
+
+    // en passant takes the pawn behind the square
+    let mut board =
+        BoardState::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").expect("Fail during board setup");
+    let piece_move = parse_san(&mut board, "exd6").expect("Fail during san parsing");
+
+    board.make_move(piece_move);
+    assert_eq!(board.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+}
+
+#[test]
+fn special_moves_incremental() {
+    let network = Arc::new(test_network(32));
+    let mut board = BoardState::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1")
+        .expect("Fail during board setup");
+
+    board.set_network(Some(Arc::clone(&network)));
+
+    let initial = board.to_fen();
+
+    // castling both ways, every promotion with and without a capture and en passant
+    assert_eq!(board.generate_legal_moves().len(), 36);
+
+    for piece_move in board.generate_legal_moves() {
+        board.make_move(piece_move);
+
+        assert_eq!(board.key, compute_key(&board));
+        assert_eq!(board.pawn_key, compute_pawn_key(&board));
+        assert_eq!(
+            (board.material, board.piece_square),
+            board.psq_tables.totals(&board)
+        );
+        assert_eq!(
+            board
+                .nnue
+                .as_ref()
+                .expect("Fail during network setup")
+                .accumulator
+                .values,
+            Accumulator::new(&network, &board).values
+        );
+
+        let _ = board.undo_move();
+
+        assert_eq!(board.to_fen(), initial);
+        assert_eq!(board.key, compute_key(&board));
+    }
+
+    // taking the rook on a8 takes black's queenside castling with it
+    let capture = parse_san(&mut board, "bxa8=Q+").expect("Fail during san parsing");
+    board.make_move(capture);
+    assert_eq!(board.to_fen(), "Q3k2r/8/8/3pP3/8/8/8/R3K2R b KQk - 0 1");
+}
+
+// leaf nodes of the legal move tree, counts are the usual perft results
+fn perft(board: &mut BoardState, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    board
+        .generate_legal_moves()
+        .into_iter()
+        .map(|piece_move| {
+            board.make_move(piece_move);
+            let nodes = perft(board, depth - 1);
+            let _ = board.undo_move();
+
+            nodes
+        })
+        .sum()
+}
+
+#[test]
+fn perft_counts() {
+    for (fen, depth, nodes) in [
+        (DEFAULT_FEN_STRING, 3, 8_902),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            3,
+            97_862,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43_238),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+            9_467,
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            3,
+            62_379,
+        ),
+    ] {
+        let mut board = BoardState::from_fen(fen).expect("Fail during board setup");
+        let key = board.key;
+
+        assert_eq!(perft(&mut board, depth), nodes, "{}", fen);
+        assert_eq!(board.key, key);
+        assert_eq!(
+            board.to_fen(),
+            BoardState::from_fen(fen)
+                .expect("Fail during board setup")
+                .to_fen()
+        );
+    }
+}
+
+#[test]
+fn epd_positions() {
+    let position = parse_epd_line(
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; am Kf2 a1a2; id \"mate; one\"; c0 \"back rank\"; fmvn 30;",
+    )
+    .expect("Fail during epd parsing");
+
+    assert_eq!(position.id(), Some("mate; one"));
+    assert_eq!(
+        position.operation("c0"),
+        Some(&["back rank".to_string()][..])
+    );
+    assert_eq!(position.board.fullmove, 30);
+
+    let a1a8 = find_move(&position.board, "a1a8");
+    let a1a2 = find_move(&position.board, "a1a2");
+    let g1f2 = find_move(&position.board, "g1f2");
+
+    // avoid moves may be written in coordinate notation too
+    assert_eq!(position.moves("am"), Ok(vec![g1f2, a1a2]));
+    assert_eq!(position.check(a1a8), Ok(EpdOutcome::Pass));
+    assert_eq!(position.check(a1a2), Ok(EpdOutcome::Fail));
+
+    let avoid_only =
+        parse_epd_line("6k1/5ppp/8/8/8/8/8/R5K1 w - - am Kf2;").expect("Fail during epd parsing");
+    assert_eq!(avoid_only.check(a1a2), Ok(EpdOutcome::Pass));
+    assert_eq!(avoid_only.check(g1f2), Ok(EpdOutcome::Fail));
+
+    let nothing =
+        parse_epd_line("6k1/5ppp/8/8/8/8/8/R5K1 w - - id \"x\";").expect("Fail during epd parsing");
+    assert_eq!(nothing.has_expectations(), Ok(false));
+    assert_eq!(nothing.check(a1a8), Ok(EpdOutcome::Skip));
+    assert_eq!(position.has_expectations(), Ok(true));
+    assert_eq!(avoid_only.has_expectations(), Ok(true));
+
+    let unknown =
+        parse_epd_line("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Qa8;").expect("Fail during epd parsing");
+    assert_eq!(unknown.has_expectations(), Err("Illegal move!"));
+    assert_eq!(unknown.check(a1a8), Err("Illegal move!"));
+
+    assert!(parse_epd_line("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8").is_err());
+    assert!(parse_epd_line("6k1/5ppp/8/8 w -").is_err());
+    // a full fen with its move counters isn't an epd line
+    assert_eq!(
+        parse_epd_line("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1 bm Ra8#;").err(),
+        Some("EPD positions have four FEN fields, move counters go in hmvc and fmvn!")
+    );
+    assert!(parse_epd_line("6k1/5ppp/8/8/8/8/8/R5K1 w - -").is_ok());
+}
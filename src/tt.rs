@@ -0,0 +1,131 @@
+use crate::{board::EncodedMove, eval::CHECKMATE, search::MAX_PLY};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const DEFAULT_TT_SIZE_MB: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    Exact = 0,
+    Lower = 1,
+    Upper = 2,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TTEntry {
+    pub best_move: Option<EncodedMove>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+// entry data is packed into one word and the key is stored xored with it,
+// so a slot torn by two threads writing at once just fails the key check
+struct TTSlot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// shared between search threads without locks
+pub struct TranspositionTable {
+    slots: Vec<TTSlot>,
+}
+
+fn pack_entry(best_move: Option<EncodedMove>, score: i32, depth: u8, bound: Bound) -> u64 {
+    // move 0 would be a8 to a8, so it is free to mean no move
+    // moves take 23 bits with the promotion piece and scores fit easily in 24
+    (best_move.unwrap_or(0) as u64 & 0x7FFFFF)
+        | (score as u32 as u64 & 0xFFFFFF) << 23
+        | (depth as u64) << 47
+        | (bound as u64) << 55
+}
+
+fn unpack_entry(data: u64) -> TTEntry {
+    let best_move = (data & 0x7FFFFF) as EncodedMove;
+
+    TTEntry {
+        best_move: if best_move == 0 {
+            None
+        } else {
+            Some(best_move)
+        },
+        // shifted up and back down to bring the sign back
+        score: ((((data >> 23) & 0xFFFFFF) as u32) << 8) as i32 >> 8,
+        depth: ((data >> 47) & 0xFF) as u8,
+        bound: match (data >> 55) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let slots_count = (size_mb * 1024 * 1024 / std::mem::size_of::<TTSlot>()).max(1);
+
+        TranspositionTable {
+            slots: (0..slots_count)
+                .map(|_| TTSlot {
+                    key: AtomicU64::new(0),
+                    data: AtomicU64::new(0),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let slot = &self.slots[(key % self.slots.len() as u64) as usize];
+
+        let data = slot.data.load(Ordering::Relaxed);
+
+        if slot.key.load(Ordering::Relaxed) ^ data != key || data == 0 {
+            return None;
+        }
+
+        Some(unpack_entry(data))
+    }
+
+    pub fn store(
+        &self,
+        key: u64,
+        best_move: Option<EncodedMove>,
+        score: i32,
+        depth: u8,
+        bound: Bound,
+    ) {
+        let slot = &self.slots[(key % self.slots.len() as u64) as usize];
+
+        // keep deeper entries of the same position, always replace other positions
+        if let Some(entry) = self.probe(key) {
+            if entry.depth > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+
+        let data = pack_entry(best_move, score, depth, bound);
+
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+// mate scores are stored relative to the node, not to the root
+pub fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= CHECKMATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -CHECKMATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+pub fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= CHECKMATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -CHECKMATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{
+    board::BoardState,
+    eval::{trace, PieceSquareTables},
+    eval_params::EvalParams,
+    pawn_hash::PawnHashTable,
+};
+use std::{fs, process::exit, sync::Arc};
+
+pub const DEFAULT_TUNE_ITERATIONS: usize = 100;
+pub const DEFAULT_TUNE_OUTPUT: &str = "tuned_params.rs";
+
+pub struct TuningPosition {
+    pub board: BoardState,
+    // game result for white, 1.0 win, 0.5 draw, 0.0 loss
+    pub result: f64,
+}
+
+// one quiet position per line, "<fen> <result>" where the result is 1-0, 0-1, 1/2-1/2
+// or 1.0, 0.5, 0.0 from the side of white, it may be wrapped in [] or "" and end with ;
+pub fn parse_tuning_line(line: &str) -> Result<TuningPosition, &'static str> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+
+    if fields.len() < 5 {
+        return Err("Incorrect tuning position!");
+    }
+
+    let result = match fields[fields.len() - 1].trim_matches(|c| "[]\";".contains(c)) {
+        "1-0" | "1.0" | "1" => 1.0,
+        "0-1" | "0.0" | "0" => 0.0,
+        "1/2-1/2" | "0.5" => 0.5,
+        _ => return Err("Incorrect game result in a tuning position!"),
+    };
+
+    // move counters don't matter for the eval and may be anything in the file
+    let board = BoardState::from_fen(&format!("{} 0 1", fields[..4].join(" ")))?;
+
+    Ok(TuningPosition { board, result })
+}
+
+pub fn load_positions(path: &str) -> Vec<TuningPosition> {
+    let content = fs::read_to_string(path).unwrap_or_else(|err| {
+        println!("{}", err);
+        exit(1);
+    });
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            parse_tuning_line(line).unwrap_or_else(|err| {
+                println!("{} ({})", err, line);
+                exit(1);
+            })
+        })
+        .collect()
+}
+
+// expected result for white from a score in centipawns, k scales it to the data
+pub fn sigmoid(score: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0))
+}
+
+// mean squared error between the game results and the sigmoid of the eval
+pub fn tuning_error(
+    positions: &mut [TuningPosition],
+    params: &EvalParams,
+    k: f64,
+    pawn_table: &mut PawnHashTable,
+) -> f64 {
+    pawn_table.clear();
+
+    let psq_tables = Arc::new(PieceSquareTables::new(params));
+
+    positions
+        .iter_mut()
+        .map(|position| {
+            position.board.set_psq_tables(Arc::clone(&psq_tables));
+
+            let score = trace(&position.board, pawn_table, params).score();
+
+            (position.result - sigmoid(score, k)).powi(2)
+        })
+        .sum::<f64>()
+        / positions.len().max(1) as f64
+}
+
+// k with the lowest error for the starting params
+fn find_k(
+    positions: &mut [TuningPosition],
+    params: &EvalParams,
+    pawn_table: &mut PawnHashTable,
+) -> f64 {
+    let mut best_k = 1.0;
+    let mut best_error = f64::MAX;
+
+    // coarse scan from 0.1 to 3.0 first and then a finer one around the best k
+    for pass in 0..2 {
+        let candidates: Vec<f64> = match pass {
+            0 => (1..=30).map(|step| step as f64 * 0.1).collect(),
+            _ => (-10..=10).map(|step| best_k + step as f64 * 0.01).collect(),
+        };
+
+        for k in candidates {
+            let error = tuning_error(positions, params, k, pawn_table);
+
+            if k > 0.0 && error < best_error {
+                best_error = error;
+                best_k = k;
+            }
+        }
+    }
+
+    best_k
+}
+
+fn param_count(params: &mut EvalParams) -> usize {
+    params
+        .fields_mut()
+        .iter()
+        .map(|(_, _, values)| values.len())
+        .sum()
+}
+
+fn param_mut(params: &mut EvalParams, index: usize) -> &mut i32 {
+    params
+        .fields_mut()
+        .into_iter()
+        .flat_map(|(_, _, values)| values)
+        .nth(index)
+        .unwrap_or_else(|| {
+            println!("Incorrect parameter index!");
+            exit(1);
+        })
+}
+
+// params as rust consts, named after the consts they replace
+pub fn params_to_rust(params: &mut EvalParams, error: f64, positions: usize) -> String {
+    let mut output = format!(
+        "// generated by `ravissant tune`, mean squared error {:.6} over {} positions\n",
+        error, positions
+    );
+
+    for (name, rust_type, values) in params.fields_mut() {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+
+        let value = match rust_type {
+            "i32" => values[0].clone(),
+            "(i32, i32)" => format!("({}, {})", values[0], values[1]),
+            // square tables one rank per line like in eval.rs
+            "[i32; 64]" => format!(
+                "[\n{}]",
+                values
+                    .chunks(8)
+                    .map(|rank| format!("    {},\n", rank.join(", ")))
+                    .collect::<String>()
+            ),
+            _ => format!("[{}]", values.join(", ")),
+        };
+
+        output += &format!("\npub const {}: {} = {};\n", name, rust_type, value);
+    }
+
+    output
+}
+
+// texel tuning, every param is moved by one in both directions and kept wherever the
+// error goes down, until a whole pass changes nothing or the iterations run out.
+// the params are written out after every pass
+pub fn tune(path: &str, iterations: usize, output: &str, start_params: &EvalParams) {
+    let mut positions = load_positions(path);
+
+    if positions.is_empty() {
+        println!("No tuning positions!");
+        exit(1);
+    }
+
+    let mut params = start_params.clone();
+    let mut pawn_table = PawnHashTable::new(1);
+
+    let k = find_k(&mut positions, &params, &mut pawn_table);
+    let mut best_error = tuning_error(&mut positions, &params, k, &mut pawn_table);
+
+    println!(
+        "positions: {}, k: {:.2}, error: {:.6}",
+        positions.len(),
+        k,
+        best_error
+    );
+
+    for iteration in 1..=iterations {
+        let mut improved = false;
+
+        for index in 0..param_count(&mut params) {
+            for delta in [1, -1] {
+                *param_mut(&mut params, index) += delta;
+
+                let error = tuning_error(&mut positions, &params, k, &mut pawn_table);
+
+                if error < best_error {
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+
+                *param_mut(&mut params, index) -= delta;
+            }
+        }
+
+        println!("iteration: {}, error: {:.6}", iteration, best_error);
+
+        fs::write(
+            output,
+            params_to_rust(&mut params, best_error, positions.len()),
+        )
+        .unwrap_or_else(|err| {
+            println!("{}", err);
+            exit(1);
+        });
+
+        if !improved {
+            break;
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::board::{Bitboard, Color, Piece};
+use crate::board::{Bitboard, Color, EncodedMove, Piece};
 
 pub fn print_bitboard(bb: Bitboard) {
     let formatted_bb: String = format!("{:064b}", bb);
@@ -57,3 +57,18 @@ pub fn number_to_color(color: u32) -> Result<Color, &'static str> {
         _ => Err("Color wasn't found when converting!"),
     }
 }
+
+// square 0 is a8, square 63 is h1
+pub fn square_to_string(square: u32) -> String {
+    format!("{}{}", (b'a' + (square % 8) as u8) as char, 8 - square / 8)
+}
+
+// coordinate notation like e2e4, promotions end with the piece like e7e8q
+pub fn move_to_string(piece_move: EncodedMove) -> String {
+    let promotion = match (piece_move >> 19) & 1 {
+        1 => ["", "n", "b", "r", "q"][((piece_move >> 20) & 7).min(4) as usize],
+        _ => "",
+    };
+
+    square_to_string(piece_move & 63) + &square_to_string((piece_move >> 6) & 63) + promotion
+}
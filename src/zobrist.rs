@@ -0,0 +1,107 @@
+use crate::{
+    board::{Bitboard, BoardState, Color, Piece},
+    utils::number_to_piece,
+};
+
+// 12 * 64 piece keys + side to move + 4 castling rights + 8 en passant files
+pub const ZOBRIST_KEYS_SIZE: usize = 781;
+
+pub const SIDE_KEY_INDEX: usize = 768;
+pub const CASTLING_KEY_INDEX: usize = 769;
+pub const EN_PASSANT_KEY_INDEX: usize = 773;
+
+// keys are generated at compile time with xorshift64*, so they are the same on every run
+const fn generate_keys(seed: u64) -> [u64; ZOBRIST_KEYS_SIZE] {
+    let mut keys = [0; ZOBRIST_KEYS_SIZE];
+    let mut state = seed;
+    let mut index = 0;
+
+    while index < ZOBRIST_KEYS_SIZE {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        keys[index] = state.wrapping_mul(0x2545F4914F6CDD1D);
+        index += 1;
+    }
+
+    keys
+}
+
+pub const ZOBRIST_KEYS: [u64; ZOBRIST_KEYS_SIZE] = generate_keys(0x5261766973736E74);
+
+pub fn piece_key(color: Color, piece: Piece, square: u32) -> u64 {
+    ZOBRIST_KEYS[(color as usize * 6 + piece as usize) * 64 + square as usize]
+}
+
+pub fn side_key() -> u64 {
+    ZOBRIST_KEYS[SIDE_KEY_INDEX]
+}
+
+pub fn castling_key(bb_castling_rigths: [[Bitboard; 2]; 2]) -> u64 {
+    let mut key = 0;
+
+    for color in 0..2 {
+        for side in 0..2 {
+            if bb_castling_rigths[color][side] != 0 {
+                key ^= ZOBRIST_KEYS[CASTLING_KEY_INDEX + color * 2 + side];
+            }
+        }
+    }
+
+    key
+}
+
+pub fn en_passant_key(bb_en_passant: Bitboard) -> u64 {
+    let mut key = 0;
+    let mut bb_squares = bb_en_passant;
+
+    while bb_squares != 0 {
+        let square = bb_squares.trailing_zeros();
+
+        key ^= ZOBRIST_KEYS[EN_PASSANT_KEY_INDEX + (square % 8) as usize];
+
+        bb_squares &= bb_squares - 1;
+    }
+
+    key
+}
+
+// full key computation, make_move and undo_move keep the key up to date incrementally
+pub fn compute_key(board: &BoardState) -> u64 {
+    let mut key = 0;
+
+    for color in [Color::White, Color::Black] {
+        for index in 0..6 {
+            let piece = number_to_piece(index).unwrap_or(Piece::None);
+            let mut bb_pieces = board.get_piece_bb(color, piece);
+
+            while bb_pieces != 0 {
+                key ^= piece_key(color, piece, bb_pieces.trailing_zeros());
+
+                bb_pieces &= bb_pieces - 1;
+            }
+        }
+    }
+
+    if matches!(board.to_move, Color::Black) {
+        key ^= side_key();
+    }
+
+    key ^ castling_key(board.bb_castling_rigths) ^ en_passant_key(board.bb_en_passant)
+}
+
+pub fn compute_pawn_key(board: &BoardState) -> u64 {
+    let mut key = 0;
+
+    for color in [Color::White, Color::Black] {
+        let mut bb_pawns = board.get_piece_bb(color, Piece::Pawn);
+
+        while bb_pawns != 0 {
+            key ^= piece_key(color, Piece::Pawn, bb_pawns.trailing_zeros());
+
+            bb_pawns &= bb_pawns - 1;
+        }
+    }
+
+    key
+}